@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::geom::Vec2;
+use crate::layout;
+use crate::polylines::Polyline;
+
+/// What a polyline represents, so backends can color or layer it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    Outline,
+    Slit,
+    BoundingBox,
+    /// Assembly marks such as labels and orientation arrows. These are
+    /// meant to be drawn or engraved, not cut.
+    Label,
+}
+
+impl Style {
+    /// Color as RGB from 0 to 1, for formats that don't keep styles apart
+    /// in groups or layers. Everything to cut is black and the marks stand
+    /// out in the colors SVG gives them, so a cutter can be set to skip
+    /// them.
+    pub fn color(self) -> (f64, f64, f64) {
+        match self {
+            Style::Outline | Style::Slit => (0.0, 0.0, 0.0),
+            Style::BoundingBox => (0.5, 0.5, 0.5),
+            Style::Label => (0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn is_cut(self) -> bool {
+        matches!(self, Style::Outline | Style::Slit)
+    }
+}
+
+/// A drawing target for the printer. Coordinates are in points with the
+/// origin at the bottom left corner of the page.
+///
+/// The printer calls the methods in this order:
+///
+/// ```text
+/// (begin_page (begin_slice (draw_polyline | draw_text)* end_slice)*
+///     end_page)* finish
+/// ```
+pub trait Backend {
+    /// Start a new page of the given size in points.
+    fn begin_page(&mut self, width: f64, height: f64) -> io::Result<()>;
+
+    /// Start a new piece whose local origin is at `offset` on the page.
+    /// Only formats that group geometry per piece need to override this.
+    fn begin_slice(&mut self, _offset: Vec2) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn draw_polyline(&mut self, polyline: &Polyline, style: Style)
+        -> io::Result<()>;
+
+    /// Draw a label with the left end of its baseline at `position`. `size`
+    /// is the font size in points and `angle` turns the baseline
+    /// counterclockwise, in degrees.
+    fn draw_text(&mut self, text: &str, position: Vec2, size: f64, angle: f64)
+        -> io::Result<()>;
+
+    fn end_slice(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> io::Result<()>;
+
+    /// Called once after the last page to write trailers and flush.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Backends that write several files ask for each one by number,
+/// starting at 1.
+pub type OpenFile<W> = Box<dyn FnMut(usize) -> io::Result<W>>;
+
+/// Open slicetest-1.svg, slicetest-2.svg, ... next to `fname`
+pub fn numbered_files(fname: &str, extension: &'static str)
+        -> OpenFile<BufWriter<File>> {
+    let fname = fname.to_string();
+    Box::new(move |number| {
+        let numbered = layout::numbered_fname(&fname, number, extension);
+        Ok(BufWriter::new(File::create(numbered)?))
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::printer::Printer;
+    use crate::registry::Registry;
+    use crate::slicer::Slicer;
+
+    /// Draw one page of US letter the way the printer does, with a piece an
+    /// inch square an inch in from the corner, a slit down from its top,
+    /// its label and the arrow next to it
+    pub fn draw_piece(backend: &mut impl Backend) -> io::Result<()> {
+        backend.begin_page(612.0, 792.0)?;
+        backend.begin_slice(Vec2(72.0, 72.0))?;
+        let outline = Polyline::new(&[
+            Vec2(72.0, 72.0),
+            Vec2(144.0, 72.0),
+            Vec2(144.0, 144.0),
+            Vec2(72.0, 144.0),
+        ], true);
+        backend.draw_polyline(&outline, Style::Outline)?;
+        let slit =
+            Polyline::new(&[Vec2(108.0, 144.0), Vec2(108.0, 108.0)], false);
+        backend.draw_polyline(&slit, Style::Slit)?;
+        backend.draw_text("X1", Vec2(76.0, 76.0), 10.0, 0.0)?;
+        let arrow = Polyline::new(&[
+            Vec2(92.0, 76.0),
+            Vec2(98.0, 76.0),
+            Vec2(95.0, 86.0),
+        ], true);
+        backend.draw_polyline(&arrow, Style::Label)?;
+        backend.end_slice()?;
+        backend.end_page()?;
+        backend.finish()
+    }
+
+    /// Files written to memory by backends that open their own, numbered
+    /// from 1 in the order they were opened
+    #[derive(Clone, Default)]
+    pub struct MemoryFiles(Rc<RefCell<Vec<Vec<u8>>>>);
+
+    pub struct MemoryFile {
+        files: MemoryFiles,
+        index: usize,
+    }
+
+    impl MemoryFiles {
+        pub fn opener(&self) -> OpenFile<MemoryFile> {
+            let files = self.clone();
+            Box::new(move |number| {
+                let mut all = files.0.borrow_mut();
+                assert_eq!(number, all.len() + 1, "files open in order");
+                all.push(Vec::new());
+                Ok(MemoryFile { files: files.clone(), index: number - 1 })
+            })
+        }
+
+        pub fn contents(&self) -> Vec<String> {
+            self.0
+                .borrow()
+                .iter()
+                .map(|file| String::from_utf8(file.clone()).unwrap())
+                .collect()
+        }
+    }
+
+    impl io::Write for MemoryFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.files.0.borrow_mut()[self.index].extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Notes which method was called with one letter each
+    struct Recorder(Rc<RefCell<String>>);
+
+    impl Recorder {
+        fn note(&mut self, call: char) -> io::Result<()> {
+            self.0.borrow_mut().push(call);
+            Ok(())
+        }
+    }
+
+    impl Backend for Recorder {
+        fn begin_page(&mut self, _width: f64, _height: f64) -> io::Result<()> {
+            self.note('[')
+        }
+
+        fn begin_slice(&mut self, _offset: Vec2) -> io::Result<()> {
+            self.note('(')
+        }
+
+        fn draw_polyline(&mut self, _polyline: &Polyline, _style: Style)
+                -> io::Result<()> {
+            self.note('p')
+        }
+
+        fn draw_text(&mut self, _text: &str, _position: Vec2, _size: f64,
+                _angle: f64) -> io::Result<()> {
+            self.note('t')
+        }
+
+        fn end_slice(&mut self) -> io::Result<()> {
+            self.note(')')
+        }
+
+        fn end_page(&mut self) -> io::Result<()> {
+            self.note(']')
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            self.note('.')
+        }
+    }
+
+    #[test]
+    fn printer_calls_in_order() {
+        let model = Registry::bundled().construct("nine_tines").unwrap();
+        let slicer = Slicer::new(1, 3, model.shape);
+        let calls = Rc::new(RefCell::new(String::new()));
+        Printer::new(Recorder(Rc::clone(&calls)), 2.0, true)
+            .print_slices(&slicer)
+            .unwrap();
+
+        let calls = calls.borrow();
+        assert!(calls.ends_with("]."), "{}", calls);
+        let pages: Vec<&str> = calls
+            .trim_end_matches('.')
+            .split_terminator(']')
+            .collect();
+        assert!(!pages.is_empty());
+        for page in pages {
+            let slices = page.strip_prefix('[').expect(page);
+            for slice in slices.split_terminator(')') {
+                let drawing = slice.strip_prefix('(').expect(slice);
+                assert!(!drawing.is_empty());
+                let drawn = drawing.chars().all(|c| c == 'p' || c == 't');
+                assert!(drawn, "{}", slice);
+            }
+        }
+    }
+}
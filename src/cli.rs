@@ -0,0 +1,1071 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::domain::Domain;
+use crate::geom::Vec2;
+use crate::models::{self, Model, ModelError, ModelSource};
+use crate::registry::Registry;
+use crate::solids::Shape;
+use crate::page::{Margins, Orientation, PageFormat, PaperSize};
+use crate::polylines::Join;
+use crate::positions::SlicePositions;
+use crate::remap::HeightMap;
+use crate::units::{self, Unit};
+use crate::validation::Limits;
+
+pub const USAGE: &str = "\
+Usage: sliceform-surfaces <command> [options]
+
+Commands:
+    template      Slice a model and write a printable template
+    preview       Write an OBJ mesh previewing the surface
+    list-models   List the available models, or search them with
+                  --search <text>
+    help          Show this message
+
+Template options:
+    --model <name|file>     Model to slice, by name or a .json model file
+                            (default: nine_tines)
+    --expr <formula>        Slice z = f(x, y) instead of a model, e.g.
+                            'exp(-4 * r^2) * (1 + cos(6 * theta)) / 2'
+    --slice-res <n>         Slice resolution, 2^(n + 1) - 1 slices
+    --slices <spec>         Slices along both axes, as a count like 5, a
+                            list of positions from 0 to 1 like 0.2,0.5,0.6,
+                            or a count and a spacing formula like 9:x^2 that
+                            maps even spacing to where the slices go
+    --x-slices <spec>, --y-slices <spec>
+                            Override the X slices (placed along y) or the Y
+                            slices (placed along x)
+    --radial <n>            Cut n slices through the middle at equal angles
+                            instead of X and Y slices. Only for round
+                            models, which all slices come out the same for
+    --rings <spec>          Add rings around the middle that bend into
+                            circles and lock into the radial slices. Like
+                            --slices, with positions from the middle (0) to
+                            the edge (1).
+    --angles <degrees,...>  Cut two or three families of slices at these
+                            angles from the x-axis instead, like 0,60,120
+                            for a triangular lattice or 45,135 for
+                            diagonals. Each family has the slices given by
+                            --slices (height fields only).
+    --domain <shape>        Stand the model on a footprint other than the
+                            square: circle, an ellipse like ellipse:1,0.6
+                            (width and depth from 0 to 1), or a polygon
+                            like polygon:0.5,0;1,1;0,1 (corners in the unit
+                            square, with edges that don't cross and can all
+                            be seen from the middle). Slices are trimmed to
+                            it, so the ones near the edge are shorter
+                            (height fields only, models may set one).
+    --range <unit|fit|low,high>
+                            Heights of the surface that become the bottom
+                            and top of the model. fit uses the lowest and
+                            highest sampled, so surfaces that dip below 0
+                            keep their detail (default: unit, 0 to 1)
+    --limit <clamp|smoothstep|tanh>
+                            How heights past the range are brought in: cut
+                            off flat, cut off and eased in, or squashed
+                            softly (default: clamp)
+    --curve <linear|gamma:<g>|log:<k>>
+                            Reshape the heights, e.g. gamma:2 to flatten
+                            the low ground or log:10 to lift it
+                            (default: linear)
+    --floor <length>        Lowest height of the model, so no slice
+                            pinches to nothing (height fields only)
+    --curve-res <n>         Curve resolution, 2^(n + 1) + 1 samples
+    --tolerance <length>    Sample curves adaptively instead, so the printed
+                            outline stays this close to the surface, e.g.
+                            0.2mm. Flat stretches get few points and sharp
+                            features get many (height fields only).
+    --width <inches>        Width of the model along x, which is how long
+                            the X slices print
+                            Models recommend these, otherwise they are 2, 6
+                            and 2.5
+    --depth <inches>        Depth of the model along y, which is how long
+                            the Y slices print (default: the width)
+    --height <inches>       Height of the model where the surface is at its
+                            top, e.g. --width 10 --depth 6 --height 3 for a
+                            low relief (default: the width)
+    --thickness <length>    Material thickness. Slits become notches this
+                            wide (default: 0, a single cut line)
+    --clearance <length>    Extra width added to each notch (default: 0)
+    --split <ratio>         How far up the material crossing slices meet.
+                            X slices are slit from the top down to there
+                            and Y slices from the bottom up (default: 0.5)
+    --base <length>         Stand every slice on a strip this tall so the
+                            model has a flat bottom (height fields only)
+    --base-plate            Also cut a plate for the slices to stand in.
+                            Tabs under the slices lock into slots in it.
+                            Needs --base and --thickness.
+    --kerf <length>         Width of material removed by the cutter. Outlines
+                            grow and notches shrink to compensate (default: 0)
+    --kerf-join <miter|round>
+                            How to join corners when offsetting outlines
+                            (default: miter)
+    --min-width <length>    Warn about material narrower than this, like
+                            thin spikes (default: 0.05in)
+    --min-slit-gap <length> Warn about slits closer together than this
+                            (default: 0.05in)
+    --min-joint <length>    Warn when less than this much material is left
+                            past the end of a slit (default: 0.05in)
+                            0 turns a check off. Slits that cut a piece in
+                            two are errors.
+    --bboxes                Draw the bounding box of each slice
+    --no-labels             Don't label the pieces and slits
+    --spacing <length>      Gap left between pieces (default: 0.1in)
+    --rotate                Let pieces turn 90 degrees to fit more per page
+    --paper <size>          letter, legal, tabloid, a4, a3, or a custom size
+                            like 600x400mm or 8.5x11in (default: letter)
+    --orientation <portrait|landscape>
+                            Turn the paper (default: as given)
+    --margin <length>       Margin on every side (default: 0.5in)
+    --margin-top <length>, --margin-right <length>,
+    --margin-bottom <length>, --margin-left <length>
+                            Override the margin on one side
+                            Lengths are in inches unless they end in mm
+    --format <ps|pdf|svg|dxf>
+                            Output format (default: ps)
+    --units <in|mm>         Units for SVG and DXF files (default: in)
+    --per-slice             Write one DXF file per slice
+    --output <file>         Output file (default: slicetest.<format>)
+                            SVG output writes one file per page,
+                            e.g. slicetest-1.svg
+
+Preview options:
+    --model <name|file>     Model to preview (default: nine_tines)
+    --expr <formula>        Preview z = f(x, y) instead of a model
+    --samples-x <n>         Number of vertices along x (default: 100)
+    --samples-y <n>         Number of vertices along y (default: 100)
+    --samples-z <n>         Number of vertices along the sides (default: 10)
+    --width <inches>, --depth <inches>, --height <inches>
+                            Size of the model, as for templates. Only the
+                            proportions matter: the mesh is 1 unit wide.
+    --range, --limit, --curve, --floor
+                            Map the heights, as for templates
+    --domain <shape>        Footprint of the model, as for templates. Other
+                            footprints than the square are meshed with
+                            --samples-x vertices around the edge and
+                            --samples-y rings inside it.
+    --output <file>         Output file (default: preview.obj)";
+
+// Beyond these the output is huge and takes a long time to produce
+const MAX_SLICE_RES: u32 = 6;
+const MAX_CURVE_RES: u32 = 16;
+// Past this the middle of a radial model is all notches
+const MAX_RADIAL_SLICES: usize = 32;
+
+/// What to do, along with the model to do it to, which is loaded once
+/// while the options are checked
+pub enum Command {
+    Template(Box<TemplateOptions>, Model),
+    Preview(PreviewOptions, Model),
+    /// List every model, or only those matching a search
+    ListModels(Option<String>),
+    Help,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    PostScript,
+    Pdf,
+    Svg,
+    Dxf,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::PostScript => "ps",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Dxf => "dxf",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ps" => Ok(OutputFormat::PostScript),
+            "pdf" => Ok(OutputFormat::Pdf),
+            "svg" => Ok(OutputFormat::Svg),
+            "dxf" => Ok(OutputFormat::Dxf),
+            _ => Err(String::from("expected ps, pdf, svg or dxf")),
+        }
+    }
+}
+
+pub struct TemplateOptions {
+    pub model: ModelSource,
+    pub slice_res: u32,
+    /// Positions of the X and Y slices, if not set by the slice resolution
+    pub x_slices: Option<Vec<f64>>,
+    pub y_slices: Option<Vec<f64>>,
+    /// Number of radial slices, if slicing radially
+    pub radial: Option<usize>,
+    /// Radii of the rings from 0 to 1, if slicing radially
+    pub rings: Vec<f64>,
+    /// Angles in degrees of the families of slices, if not X and Y
+    pub angles: Vec<f64>,
+    pub domain: Domain,
+    pub heights: HeightMap,
+    pub curve_res: u32,
+    /// Largest distance in points between the printed outline and the
+    /// surface, if curves are sampled adaptively
+    pub tolerance: Option<f64>,
+    /// Size of the model in inches along x, along y and up
+    pub width: f64,
+    pub depth: f64,
+    pub height: f64,
+    /// Thickness of the material and extra width of each notch in points
+    pub thickness: f64,
+    pub clearance: f64,
+    pub split: f64,
+    /// Height of the strip under each slice in points
+    pub base: f64,
+    pub base_plate: bool,
+    /// Width of material the cutter removes in points
+    pub kerf: f64,
+    pub kerf_join: Join,
+    pub limits: Limits,
+    pub draw_bboxes: bool,
+    /// Gap between pieces in points
+    pub spacing: f64,
+    pub rotate: bool,
+    pub draw_labels: bool,
+    pub format: OutputFormat,
+    pub units: Unit,
+    pub per_slice: bool,
+    pub page_format: PageFormat,
+    pub output: String,
+}
+
+impl TemplateOptions {
+    fn new() -> Self {
+        Self {
+            model: ModelSource::Named(String::from("nine_tines")),
+            slice_res: models::DEFAULT_SLICE_RES,
+            x_slices: None,
+            y_slices: None,
+            radial: None,
+            rings: Vec::new(),
+            angles: Vec::new(),
+            domain: Domain::Square,
+            heights: HeightMap::default(),
+            curve_res: models::DEFAULT_CURVE_RES,
+            tolerance: None,
+            width: models::DEFAULT_WIDTH,
+            depth: models::DEFAULT_WIDTH,
+            height: models::DEFAULT_WIDTH,
+            thickness: 0.0,
+            clearance: 0.0,
+            split: 0.5,
+            base: 0.0,
+            base_plate: false,
+            kerf: 0.0,
+            kerf_join: Join::Miter,
+            limits: Limits {
+                min_width: 0.05 * units::INCH,
+                min_slit_gap: 0.05 * units::INCH,
+                min_joint: 0.05 * units::INCH,
+            },
+            draw_bboxes: false,
+            spacing: 0.1 * units::INCH,
+            rotate: false,
+            draw_labels: true,
+            format: OutputFormat::PostScript,
+            units: Unit::Inches,
+            per_slice: false,
+            page_format: PageFormat::default(),
+            output: String::new(),
+        }
+    }
+}
+
+pub struct PreviewOptions {
+    pub model: ModelSource,
+    pub samples_x: usize,
+    pub samples_y: usize,
+    pub samples_z: usize,
+    /// Size of the model in inches along x, along y and up
+    pub width: f64,
+    pub depth: f64,
+    pub height: f64,
+    pub domain: Domain,
+    pub heights: HeightMap,
+    pub output: String,
+}
+
+impl PreviewOptions {
+    fn new() -> Self {
+        Self {
+            model: ModelSource::Named(String::from("nine_tines")),
+            samples_x: 100,
+            samples_y: 100,
+            samples_z: 10,
+            width: models::DEFAULT_WIDTH,
+            depth: models::DEFAULT_WIDTH,
+            height: models::DEFAULT_WIDTH,
+            domain: Domain::Square,
+            heights: HeightMap::default(),
+            output: String::from("preview.obj"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+        reason: String,
+    },
+    Model(ModelError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CliError::*;
+        match self {
+            MissingCommand => write!(f, "no command given"),
+            UnknownCommand(cmd) => write!(f, "unknown command '{}'", cmd),
+            UnknownOption(opt) => write!(f, "unknown option '{}'", opt),
+            MissingValue(opt) => write!(f, "option '{}' needs a value", opt),
+            InvalidValue { option, value, reason } => write!(
+                f, "invalid value '{}' for '{}': {}", value, option, reason),
+            Model(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Walks through the arguments after the command name, splitting
+/// `--option=value` into its two halves.
+struct OptionParser<'a> {
+    args: std::slice::Iter<'a, String>,
+    pending_value: Option<String>,
+}
+
+impl<'a> OptionParser<'a> {
+    fn new(args: &'a [String]) -> Self {
+        Self {
+            args: args.iter(),
+            pending_value: None,
+        }
+    }
+
+    fn next_option(&mut self) -> Result<Option<String>, CliError> {
+        let arg = match self.args.next() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+
+        if !arg.starts_with("--") {
+            return Err(CliError::UnknownOption(arg.clone()));
+        }
+
+        match arg.find('=') {
+            Some(index) => {
+                self.pending_value = Some(arg[index + 1..].to_string());
+                Ok(Some(arg[..index].to_string()))
+            },
+            None => Ok(Some(arg.clone()))
+        }
+    }
+
+    fn value(&mut self, option: &str) -> Result<String, CliError> {
+        if let Some(value) = self.pending_value.take() {
+            return Ok(value);
+        }
+
+        self.args
+            .next()
+            .cloned()
+            .ok_or_else(|| CliError::MissingValue(option.to_string()))
+    }
+
+    fn parse_value<T>(&mut self, option: &str) -> Result<T, CliError>
+            where T: FromStr, T::Err: fmt::Display {
+        let value = self.value(option)?;
+        value.parse().map_err(|err: T::Err| CliError::InvalidValue {
+            option: option.to_string(),
+            reason: err.to_string(),
+            value,
+        })
+    }
+
+    fn parse_length(&mut self, option: &str) -> Result<f64, CliError> {
+        let value = self.value(option)?;
+        units::parse_length(&value).map_err(|reason| CliError::InvalidValue {
+            option: option.to_string(),
+            value,
+            reason,
+        })
+    }
+
+    fn flag(&mut self, option: &str) -> Result<(), CliError> {
+        match self.pending_value.take() {
+            Some(value) => Err(CliError::InvalidValue {
+                option: option.to_string(),
+                value,
+                reason: String::from("this option does not take a value"),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse the command line. Models are looked up in `registry` so their
+/// recommended settings can fill in options that weren't given.
+pub fn parse_args(args: &[String], registry: &Registry)
+        -> Result<Command, CliError> {
+    let (command, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(CliError::MissingCommand),
+    };
+
+    match command.as_str() {
+        "template" => parse_template(rest, registry).map(|(options, model)| {
+            Command::Template(Box::new(options), model)
+        }),
+        "preview" => parse_preview(rest, registry)
+            .map(|(options, model)| Command::Preview(options, model)),
+        "list-models" => parse_list_models(rest).map(Command::ListModels),
+        "help" | "--help" | "-h" => Ok(Command::Help),
+        _ => Err(CliError::UnknownCommand(command.clone())),
+    }
+}
+
+fn parse_list_models(args: &[String]) -> Result<Option<String>, CliError> {
+    let mut search = None;
+    let mut parser = OptionParser::new(args);
+    while let Some(option) = parser.next_option()? {
+        match option.as_str() {
+            "--search" => search = Some(parser.value(&option)?),
+            _ => return Err(CliError::UnknownOption(option)),
+        }
+    }
+
+    Ok(search)
+}
+
+fn parse_template(args: &[String], registry: &Registry)
+        -> Result<(TemplateOptions, Model), CliError> {
+    let mut options = TemplateOptions::new();
+    let mut model = None;
+    let mut slice_res = None;
+    let mut curve_res = None;
+    let mut width = None;
+    let mut depth = None;
+    let mut height = None;
+    let mut floor = 0.0;
+    let mut domain = None;
+    let mut paper = PaperSize::LETTER;
+    let mut orientation = None;
+    let mut margins = options.page_format.margins;
+
+    let mut parser = OptionParser::new(args);
+    while let Some(option) = parser.next_option()? {
+        match option.as_str() {
+            "--model" | "--expr" => {
+                model = Some(parse_model_source(&mut parser, &option, model)?);
+            },
+            "--slice-res" => slice_res = Some(parser.parse_value(&option)?),
+            "--slices" => {
+                let spec: SlicePositions = parser.parse_value(&option)?;
+                options.x_slices = Some(spec.positions());
+                options.y_slices = Some(spec.positions());
+            },
+            "--x-slices" => {
+                let spec: SlicePositions = parser.parse_value(&option)?;
+                options.x_slices = Some(spec.positions());
+            },
+            "--y-slices" => {
+                let spec: SlicePositions = parser.parse_value(&option)?;
+                options.y_slices = Some(spec.positions());
+            },
+            "--radial" => options.radial = Some(parser.parse_value(&option)?),
+            "--rings" => {
+                let spec: SlicePositions = parser.parse_value(&option)?;
+                options.rings = spec.positions();
+            },
+            "--angles" => {
+                let value = parser.value(&option)?;
+                options.angles = value
+                    .split(',')
+                    .map(|angle| angle.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| CliError::InvalidValue {
+                        option: option.clone(),
+                        value: value.clone(),
+                        reason: String::from("must be a list of angles"),
+                    })?;
+            },
+            "--domain" => domain = Some(parser.parse_value(&option)?),
+            "--range" => options.heights.range = parser.parse_value(&option)?,
+            "--limit" => options.heights.limit = parser.parse_value(&option)?,
+            "--curve" => options.heights.curve = parser.parse_value(&option)?,
+            "--floor" => floor = parser.parse_length(&option)?,
+            "--curve-res" => curve_res = Some(parser.parse_value(&option)?),
+            "--tolerance" => {
+                options.tolerance = Some(parser.parse_length(&option)?);
+            },
+            "--width" => width = Some(parser.parse_value(&option)?),
+            "--depth" => depth = Some(parser.parse_value(&option)?),
+            "--height" => height = Some(parser.parse_value(&option)?),
+            "--thickness" => {
+                options.thickness = parser.parse_length(&option)?;
+            },
+            "--clearance" => {
+                options.clearance = parser.parse_length(&option)?;
+            },
+            "--split" => options.split = parser.parse_value(&option)?,
+            "--kerf" => options.kerf = parser.parse_length(&option)?,
+            "--kerf-join" => options.kerf_join = parser.parse_value(&option)?,
+            "--base" => options.base = parser.parse_length(&option)?,
+            "--base-plate" => {
+                parser.flag(&option)?;
+                options.base_plate = true;
+            },
+            "--min-width" => {
+                options.limits.min_width = parser.parse_length(&option)?;
+            },
+            "--min-slit-gap" => {
+                options.limits.min_slit_gap = parser.parse_length(&option)?;
+            },
+            "--min-joint" => {
+                options.limits.min_joint = parser.parse_length(&option)?;
+            },
+            "--bboxes" => {
+                parser.flag(&option)?;
+                options.draw_bboxes = true;
+            },
+            "--no-labels" => {
+                parser.flag(&option)?;
+                options.draw_labels = false;
+            },
+            "--spacing" => options.spacing = parser.parse_length(&option)?,
+            "--rotate" => {
+                parser.flag(&option)?;
+                options.rotate = true;
+            },
+            "--format" => options.format = parser.parse_value(&option)?,
+            "--units" => options.units = parser.parse_value(&option)?,
+            "--per-slice" => {
+                parser.flag(&option)?;
+                options.per_slice = true;
+            },
+            "--paper" => paper = parser.parse_value(&option)?,
+            "--orientation" => {
+                orientation = Some(parser.parse_value::<Orientation>(&option)?);
+            },
+            "--margin" => {
+                margins = Margins::uniform(parser.parse_length(&option)?);
+            },
+            "--margin-top" => margins.top = parser.parse_length(&option)?,
+            "--margin-right" => margins.right = parser.parse_length(&option)?,
+            "--margin-bottom" => {
+                margins.bottom = parser.parse_length(&option)?;
+            },
+            "--margin-left" => margins.left = parser.parse_length(&option)?,
+            "--output" => options.output = parser.value(&option)?,
+            _ => return Err(CliError::UnknownOption(option)),
+        }
+    }
+
+    let Margins { top, right, bottom, left } = margins;
+    for (option, margin) in [
+            ("--margin-top", top),
+            ("--margin-right", right),
+            ("--margin-bottom", bottom),
+            ("--margin-left", left)].iter() {
+        check(
+            option,
+            margin / units::INCH,
+            *margin >= 0.0,
+            "margins can't be negative")?;
+    }
+
+    check(
+        "--spacing",
+        options.spacing / units::INCH,
+        options.spacing >= 0.0,
+        "can't be negative")?;
+
+    let Limits { min_width, min_slit_gap, min_joint } = options.limits;
+    for (option, limit) in [
+            ("--min-width", min_width),
+            ("--min-slit-gap", min_slit_gap),
+            ("--min-joint", min_joint)].iter() {
+        check(option, limit / units::INCH, *limit >= 0.0, "can't be negative")?;
+    }
+
+    check(
+        "--split",
+        options.split,
+        options.split > 0.0 && options.split < 1.0,
+        "must be between 0 and 1")?;
+
+    if let Some(tolerance) = options.tolerance {
+        check(
+            "--tolerance",
+            tolerance / units::INCH,
+            tolerance > 0.0,
+            "must be positive")?;
+    }
+
+    options.page_format = PageFormat::new(paper, orientation, margins);
+    let page = &options.page_format;
+    check(
+        "--paper",
+        format!("{}x{}in", page.width / units::INCH, page.height / units::INCH),
+        page.printable_width() > 0.0 && page.printable_height() > 0.0,
+        "the margins leave no room to print")?;
+
+    if options.output.is_empty() {
+        options.output = format!("slicetest.{}", options.format.extension());
+    }
+
+    if let Some(model) = model {
+        options.model = model;
+    }
+    let model = validate_model(&options.model, registry)?;
+    let info = &model.info;
+    options.slice_res = slice_res.unwrap_or(info.slice_res);
+    options.curve_res = curve_res.unwrap_or(info.curve_res);
+    options.width = width.unwrap_or(info.width);
+    options.depth = depth.unwrap_or(options.width);
+    options.height = height.unwrap_or(options.width);
+    options.domain = domain.unwrap_or_else(|| info.domain.clone());
+
+    check(
+        "--format",
+        options.format.extension(),
+        !options.per_slice || options.format == OutputFormat::Dxf,
+        "only DXF output can be split into one file per slice")?;
+    check(
+        "--slice-res",
+        options.slice_res,
+        options.slice_res <= MAX_SLICE_RES,
+        &format!("must be at most {}", MAX_SLICE_RES))?;
+    check(
+        "--curve-res",
+        options.curve_res,
+        options.curve_res <= MAX_CURVE_RES,
+        &format!("must be at most {}", MAX_CURVE_RES))?;
+    check_size(options.width, options.depth, options.height)?;
+    options.heights.floor = check_floor(floor, options.height)?;
+    check(
+        "--model",
+        &info.name,
+        options.heights.is_clamp() ||
+            matches!(model.shape, Shape::HeightField(_)),
+        "--range, --limit, --curve and --floor only apply to height fields")?;
+    check(
+        "--thickness",
+        options.thickness / units::INCH,
+        options.thickness.is_finite() && options.thickness >= 0.0,
+        "can't be negative")?;
+    check(
+        "--clearance",
+        options.clearance / units::INCH,
+        options.clearance.is_finite() && options.clearance >= 0.0,
+        "can't be negative")?;
+    check(
+        "--clearance",
+        options.clearance / units::INCH,
+        options.clearance == 0.0 || options.thickness > 0.0,
+        "only applies when --thickness is set")?;
+    check(
+        "--kerf",
+        options.kerf / units::INCH,
+        options.kerf.is_finite() && options.kerf >= 0.0,
+        "can't be negative")?;
+    check(
+        "--kerf",
+        options.kerf / units::INCH,
+        options.thickness == 0.0 ||
+            options.kerf < options.thickness + options.clearance,
+        "must be narrower than the notches")?;
+    check(
+        "--base",
+        options.base / units::INCH,
+        options.base >= 0.0,
+        "can't be negative")?;
+    check(
+        "--base",
+        options.base / units::INCH,
+        options.base == 0.0 || matches!(model.shape, Shape::HeightField(_)),
+        "only height fields can stand on a base, not solids")?;
+    if let Some(tolerance) = options.tolerance {
+        check(
+            "--tolerance",
+            tolerance / units::INCH,
+            matches!(model.shape, Shape::HeightField(_)),
+            "only height fields have curves to sample, not solids")?;
+    }
+    if let Some(count) = options.radial {
+        check(
+            "--radial",
+            count,
+            (1..=MAX_RADIAL_SLICES).contains(&count),
+            &format!("must be from 1 to {}", MAX_RADIAL_SLICES))?;
+        check(
+            "--radial",
+            count,
+            matches!(model.shape, Shape::HeightField(_)),
+            "only height fields can be sliced radially, not solids")?;
+        check(
+            "--model",
+            &info.name,
+            info.round,
+            "only round models, like euclidean surfaces of revolution, can \
+            be sliced radially")?;
+        check(
+            "--domain",
+            &options.domain,
+            options.domain.is_square() || options.domain.is_circle(),
+            "radial slices need the square or a circle to stand on")?;
+        // Rings are circles around the center, so they stay on a circle
+        // if they start on it
+        for &ring in options.rings.iter() {
+            check(
+                "--rings",
+                ring,
+                options.domain.contains(Vec2(0.5 + ring / 2.0, 0.5)),
+                "the ring runs off the domain")?;
+        }
+        check(
+            "--radial",
+            count,
+            options.x_slices.is_none() && options.y_slices.is_none(),
+            "can't be used with --slices, --x-slices or --y-slices")?;
+        check(
+            "--radial",
+            count,
+            options.base == 0.0,
+            "can't be used with --base")?;
+        check(
+            "--radial",
+            count,
+            options.depth == options.width,
+            "needs a square footprint, with --depth the same as --width")?;
+    }
+    if !options.angles.is_empty() {
+        let angles = options.angles
+            .iter()
+            .map(|angle| angle.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let angle_count = options.angles.len();
+        check(
+            "--angles",
+            &angles,
+            angle_count == 2 || angle_count == 3,
+            "needs two or three angles")?;
+        // Parallel families never cross
+        let parallel = (0..angle_count).any(|i| {
+            (i + 1..angle_count).any(|j| {
+                let difference = (options.angles[i] - options.angles[j])
+                    .rem_euclid(180.0);
+                !(1.0..=179.0).contains(&difference)
+            })
+        });
+        check(
+            "--angles",
+            &angles,
+            !parallel,
+            "families must be at least 1 degree apart")?;
+        check(
+            "--angles",
+            &angles,
+            matches!(model.shape, Shape::HeightField(_)),
+            "only height fields can be sliced at angles, not solids")?;
+        check(
+            "--angles",
+            &angles,
+            options.radial.is_none() && options.base == 0.0,
+            "can't be used with --radial or --base")?;
+        check(
+            "--angles",
+            &angles,
+            options.x_slices == options.y_slices,
+            "use --slices to place the slices of every family")?;
+        check(
+            "--angles",
+            &angles,
+            options.depth == options.width,
+            "needs a square footprint, with --depth the same as --width")?;
+    }
+    if !options.domain.is_square() {
+        check(
+            "--domain",
+            &options.domain,
+            matches!(model.shape, Shape::HeightField(_)),
+            "only height fields have a domain, not solids")?;
+        check(
+            "--domain",
+            &options.domain,
+            options.base == 0.0,
+            "can't be used with --base")?;
+    }
+    check(
+        "--rings",
+        options.rings.len(),
+        options.rings.is_empty() || options.radial.is_some(),
+        "only applies when --radial is set")?;
+    check(
+        "--base",
+        options.base / units::INCH,
+        !options.base_plate || options.base > 0.0,
+        "--base-plate needs a base for the tabs to hang from")?;
+    check(
+        "--thickness",
+        options.thickness / units::INCH,
+        !options.base_plate || options.thickness > 0.0,
+        "--base-plate needs the material thickness")?;
+
+    Ok((options, model))
+}
+
+fn parse_preview(args: &[String], registry: &Registry)
+        -> Result<(PreviewOptions, Model), CliError> {
+    let mut options = PreviewOptions::new();
+    let mut model = None;
+    let mut width = None;
+    let mut depth = None;
+    let mut height = None;
+    let mut floor = 0.0;
+    let mut domain = None;
+    let mut parser = OptionParser::new(args);
+    while let Some(option) = parser.next_option()? {
+        match option.as_str() {
+            "--model" | "--expr" => {
+                model = Some(parse_model_source(&mut parser, &option, model)?);
+            },
+            "--samples-x" => options.samples_x = parser.parse_value(&option)?,
+            "--samples-y" => options.samples_y = parser.parse_value(&option)?,
+            "--samples-z" => options.samples_z = parser.parse_value(&option)?,
+            "--width" => width = Some(parser.parse_value(&option)?),
+            "--depth" => depth = Some(parser.parse_value(&option)?),
+            "--height" => height = Some(parser.parse_value(&option)?),
+            "--domain" => domain = Some(parser.parse_value(&option)?),
+            "--range" => options.heights.range = parser.parse_value(&option)?,
+            "--limit" => options.heights.limit = parser.parse_value(&option)?,
+            "--curve" => options.heights.curve = parser.parse_value(&option)?,
+            "--floor" => floor = parser.parse_length(&option)?,
+            "--output" => options.output = parser.value(&option)?,
+            _ => return Err(CliError::UnknownOption(option)),
+        }
+    }
+
+    if let Some(model) = model {
+        options.model = model;
+    }
+    let model = validate_model(&options.model, registry)?;
+    options.width = width.unwrap_or(model.info.width);
+    options.depth = depth.unwrap_or(options.width);
+    options.height = height.unwrap_or(options.width);
+    check_size(options.width, options.depth, options.height)?;
+    options.heights.floor = check_floor(floor, options.height)?;
+    check(
+        "--model",
+        &model.info.name,
+        matches!(model.shape, Shape::HeightField(_)),
+        "preview only supports height fields, not solids")?;
+    options.domain = domain.unwrap_or_else(|| model.info.domain.clone());
+    for (option, samples) in [
+            ("--samples-x", options.samples_x),
+            ("--samples-y", options.samples_y),
+            ("--samples-z", options.samples_z)].iter() {
+        check(option, samples, *samples >= 2, "must be at least 2")?;
+    }
+
+    // The preview mesh stitches the top and sides together assuming a
+    // square grid.
+    check(
+        "--samples-y",
+        options.samples_y,
+        options.samples_x == options.samples_y || !options.domain.is_square(),
+        "must match --samples-x")?;
+
+    Ok((options, model))
+}
+
+/// Handle --model or --expr. Only one of them may be given.
+fn parse_model_source(
+        parser: &mut OptionParser,
+        option: &str,
+        previous: Option<ModelSource>) -> Result<ModelSource, CliError> {
+    let value = parser.value(option)?;
+    if previous.is_some() {
+        return Err(CliError::InvalidValue {
+            option: option.to_string(),
+            value,
+            reason: String::from("only one --model or --expr can be given"),
+        });
+    }
+
+    if option == "--expr" {
+        Ok(ModelSource::Expression(value))
+    } else {
+        Ok(ModelSource::Named(value))
+    }
+}
+
+/// Load the model once so mistakes in model files and formulas show up as
+/// usage errors
+fn validate_model(model: &ModelSource, registry: &Registry)
+        -> Result<Model, CliError> {
+    model.load(registry).map_err(CliError::Model)
+}
+
+/// The floor as a fraction of the height of the model, which it must be
+/// below
+fn check_floor(floor: f64, height: f64) -> Result<f64, CliError> {
+    let fraction = floor / (height * units::INCH);
+    check(
+        "--floor",
+        floor / units::INCH,
+        (0.0..1.0).contains(&fraction),
+        "must be at least 0 and lower than the model")?;
+
+    Ok(fraction)
+}
+
+/// The width, depth and height of a model must all be positive
+fn check_size(width: f64, depth: f64, height: f64) -> Result<(), CliError> {
+    for (option, size) in [
+            ("--width", width),
+            ("--depth", depth),
+            ("--height", height)].iter() {
+        check(
+            option,
+            size,
+            size.is_finite() && *size > 0.0,
+            "must be a positive number of inches")?;
+    }
+
+    Ok(())
+}
+
+fn check<T: ToString>(option: &str, value: T, valid: bool, reason: &str)
+        -> Result<(), CliError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(CliError::InvalidValue {
+            option: option.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, CliError> {
+        let args: Vec<String> =
+            args.split_whitespace().map(String::from).collect();
+        parse_args(&args, &Registry::bundled())
+    }
+
+    fn template(args: &str) -> TemplateOptions {
+        match parse(&format!("template {}", args)) {
+            Ok(Command::Template(options, _)) => *options,
+            Ok(_) => panic!("'{}' is not a template", args),
+            Err(err) => panic!("'{}': {}", args, err),
+        }
+    }
+
+    /// The option that was rejected and why
+    fn rejected(args: &str) -> (String, String) {
+        match parse(args) {
+            Err(CliError::InvalidValue { option, reason, .. }) => {
+                (option, reason)
+            },
+            Err(err) => panic!("'{}': {}", args, err),
+            Ok(_) => panic!("'{}' was accepted", args),
+        }
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse(""), Err(CliError::MissingCommand)));
+        assert!(matches!(parse("slice"), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse("-h"), Ok(Command::Help)));
+        assert!(matches!(
+            parse("list-models --search=peak"),
+            Ok(Command::ListModels(Some(search))) if search == "peak"));
+        assert!(matches!(
+            parse("preview --model sine_hill"),
+            Ok(Command::Preview(..))));
+    }
+
+    #[test]
+    fn model_settings_fill_in_options() {
+        let options = template("--model nine_tines --width 3");
+        assert_eq!(options.width, 3.0);
+        assert_eq!(options.depth, 3.0);
+        assert_eq!(options.output, "slicetest.ps");
+    }
+
+    #[test]
+    fn lengths_take_units() {
+        let options = template(
+            "--thickness 3.175mm --clearance=0.005 --kerf 0.1mm --spacing 1in");
+        assert!((options.thickness - 9.0).abs() < 1e-9);
+        assert!((options.clearance - 0.36).abs() < 1e-9);
+        assert!((options.kerf - 72.0 / 254.0).abs() < 1e-9);
+        assert_eq!(options.spacing, 72.0);
+        assert_eq!(rejected("template --kerf 1pt").0, "--kerf");
+    }
+
+    #[test]
+    fn flags_take_no_value() {
+        let options = template("--base 0.25 --base-plate --thickness 0.125");
+        assert!(options.base_plate);
+        for &flag in ["--base-plate", "--rotate", "--bboxes", "--no-labels",
+                "--per-slice"].iter() {
+            let (option, reason) =
+                rejected(&format!("template {}=yes", flag));
+            assert_eq!(option, flag);
+            assert_eq!(reason, "this option does not take a value");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        assert!(matches!(
+            parse("template --frobnicate"),
+            Err(CliError::UnknownOption(_))));
+        assert!(matches!(
+            parse("template --width"),
+            Err(CliError::MissingValue(_))));
+
+        for &(args, expected) in [
+            ("template --slices 3000000000", "--slices"),
+            ("template --kerf -1", "--kerf"),
+            ("template --thickness 0.125 --kerf 0.2", "--kerf"),
+            ("template --clearance 0.01", "--clearance"),
+            ("template --radial 6 --model crater_diamond", "--model"),
+            ("template --radial 6 --model step_hill --domain ellipse:1,0.6",
+                "--domain"),
+            ("template --radial 6 --model step_hill --domain ellipse:0.6,0.6 \
+                --rings 0.9", "--rings"),
+            ("template --model torus --tolerance 0.2mm", "--tolerance"),
+            ("preview --model torus", "--model"),
+            // Both commands turn down footprints the preview can't mesh
+            ("template --domain polygon:0,0;1,0;1,1;0.5,0.2;0,1", "--domain"),
+            ("preview --domain polygon:0,0;1,0;1,1;0.5,0.2;0,1", "--domain"),
+        ].iter() {
+            assert_eq!(rejected(args).0, expected, "{}", args);
+        }
+    }
+}
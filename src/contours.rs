@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+
+/// A grid edge, from sample (i, j) to the next sample up if the flag is set,
+/// otherwise to the next sample right
+type EdgeKey = (usize, usize, bool);
+
+/// Finds the outlines of a 2D region given as a scalar field that is negative
+/// or zero inside, using marching squares. The region is trimmed to the unit
+/// square, so every outline is closed even where the shape runs off the edge.
+pub struct ContourTracer {
+    samples: usize,
+    refine_steps: usize,
+}
+
+impl ContourTracer {
+    /// Sample the field on a grid with `samples` cells along each side of
+    /// the unit square
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples,
+            refine_steps: 0,
+        }
+    }
+
+    /// Move each outline point onto the boundary with this many bisection
+    /// steps along its grid edge, instead of interpolating between samples
+    pub fn with_refinement(mut self, steps: usize) -> Self {
+        self.refine_steps = steps;
+        self
+    }
+
+    /// Closed outlines of the region. Outer boundaries go counterclockwise
+    /// and holes clockwise, and each outer boundary is followed by the holes
+    /// directly inside it.
+    pub fn trace(&self, field: &dyn Fn(f64, f64) -> f64) -> Vec<Polyline> {
+        let step = 1.0 / self.samples as f64;
+        // One extra ring of samples all the way around the square, which is
+        // always outside
+        let count = self.samples + 3;
+        let coord = |i: usize| (i as f64 - 1.0) * step;
+
+        // At the edges of the square this is exactly 0, so outlines run right
+        // along the edge.
+        let clipped = |u: f64, v: f64| {
+            let outside_square = (-u).max(u - 1.0).max(-v).max(v - 1.0);
+            field(u, v).max(outside_square)
+        };
+        let values: Vec<Vec<f64>> = (0..count)
+            .map(|i| (0..count).map(|j| clipped(coord(i), coord(j))).collect())
+            .collect();
+        let inside = |i: usize, j: usize| values[i][j] <= 0.0;
+
+        let crossing = |(i, j, vertical): EdgeKey| {
+            let (i2, j2) = if vertical { (i, j + 1) } else { (i + 1, j) };
+            let start = Vec2(coord(i), coord(j));
+            let end = Vec2(coord(i2), coord(j2));
+            let along = |t: f64| start.scale(1.0 - t).translate(end.scale(t));
+
+            let a = values[i][j];
+            let b = values[i2][j2];
+            let t = if self.refine_steps > 0 {
+                let is_inside = |t: f64| {
+                    let Vec2(u, v) = along(t);
+                    clipped(u, v) <= 0.0
+                };
+                bisect(&is_inside, 0.0, 1.0, self.refine_steps)
+            } else {
+                a / (a - b)
+            };
+
+            along(t)
+        };
+
+        let mut segments: Vec<(EdgeKey, EdgeKey)> = Vec::new();
+        for i in 0..count - 1 {
+            for j in 0..count - 1 {
+                let corners = [
+                    inside(i, j),
+                    inside(i + 1, j),
+                    inside(i + 1, j + 1),
+                    inside(i, j + 1),
+                ];
+                // Edge k runs from corner k to corner k + 1
+                let edges = [
+                    (i, j, false),
+                    (i + 1, j, true),
+                    (i, j + 1, false),
+                    (i, j, true),
+                ];
+
+                let crossed: Vec<EdgeKey> = (0..4)
+                    .filter(|&k| corners[k] != corners[(k + 1) % 4])
+                    .map(|k| edges[k])
+                    .collect();
+
+                match crossed.len() {
+                    2 => segments.push((crossed[0], crossed[1])),
+                    4 => {
+                        // A saddle, with opposite corners on the same side.
+                        // The average of the corners decides whether the
+                        // middle of the cell joins the inside corners or the
+                        // outside ones. The corners on the other side from
+                        // the middle get cut off on their own.
+                        let center = (values[i][j] + values[i + 1][j] +
+                            values[i + 1][j + 1] + values[i][j + 1]) / 4.0;
+                        let center_inside = center <= 0.0;
+                        for k in (0..4).filter(|&k| corners[k] != center_inside) {
+                            segments.push((edges[(k + 3) % 4], edges[k]));
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        let outlines: Vec<Polyline> = link_segments(&segments)
+            .into_iter()
+            .filter(|chain| chain.len() >= 3)
+            .map(|chain| {
+                let points: Vec<Vec2> = chain.into_iter().map(crossing).collect();
+                Polyline::new(&points, true)
+            })
+            .collect();
+
+        nest(outlines)
+    }
+}
+
+/// Join segments that share endpoints into closed loops. Every endpoint of a
+/// marching squares segment is shared by exactly two segments.
+fn link_segments(segments: &[(EdgeKey, EdgeKey)]) -> Vec<Vec<EdgeKey>> {
+    let mut by_key: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+    for (index, (a, b)) in segments.iter().enumerate() {
+        by_key.entry(*a).or_default().push(index);
+        by_key.entry(*b).or_default().push(index);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut loops = Vec::new();
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+
+        used[first] = true;
+        let (start, mut current) = segments[first];
+        let mut chain = vec![start];
+        while current != start {
+            chain.push(current);
+            let next = by_key[&current]
+                .iter()
+                .copied()
+                .find(|&index| !used[index]);
+            let Some(index) = next else { break };
+
+            used[index] = true;
+            let (a, b) = segments[index];
+            current = if a == current { b } else { a };
+        }
+
+        loops.push(chain);
+    }
+
+    loops
+}
+
+/// Orient the outlines and put each outer boundary before its holes. An
+/// outline inside an odd number of others is a hole. Islands inside holes
+/// are outer boundaries again.
+fn nest(outlines: Vec<Polyline>) -> Vec<Polyline> {
+    let contains = |outer: &Polyline, inner: &Polyline| {
+        inner.vertices().first().is_some_and(|v| outer.contains(*v))
+    };
+    let depths: Vec<usize> = outlines
+        .iter()
+        .enumerate()
+        .map(|(i, inner)| {
+            outlines
+                .iter()
+                .enumerate()
+                .filter(|(j, outer)| i != *j && contains(outer, inner))
+                .count()
+        })
+        .collect();
+
+    // Each outer boundary followed by the holes directly inside it
+    let mut order = Vec::new();
+    for (i, outer) in outlines.iter().enumerate() {
+        if depths[i] % 2 == 1 {
+            continue;
+        }
+
+        order.push(i);
+        order.extend((0..outlines.len()).filter(|&j| {
+            depths[j] == depths[i] + 1 && contains(outer, &outlines[j])
+        }));
+    }
+
+    let mut outlines: Vec<Option<Polyline>> =
+        outlines.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|i| {
+            let outline = outlines[i].take()?;
+            let counterclockwise = depths[i].is_multiple_of(2);
+            if outline.is_counterclockwise() == counterclockwise {
+                Some(outline)
+            } else {
+                Some(outline.reversed())
+            }
+        })
+        .collect()
+}
+
+/// The stretches of [0, 1] where `field` is negative, found by sampling at
+/// `samples` steps and then bisecting each crossing
+pub fn find_spans(field: &dyn Fn(f64) -> f64, samples: usize) -> Vec<(f64, f64)> {
+    const BISECTION_STEPS: usize = 30;
+
+    let inside = |t: f64| field(t) < 0.0;
+    let mut spans = Vec::new();
+    let mut start = if inside(0.0) { Some(0.0) } else { None };
+
+    let mut previous = 0.0;
+    for i in 1..=samples {
+        let t = i as f64 / samples as f64;
+        if inside(t) != start.is_some() {
+            let crossing = bisect(&inside, previous, t, BISECTION_STEPS);
+            match start.take() {
+                Some(begin) => spans.push((begin, crossing)),
+                None => start = Some(crossing),
+            }
+        }
+        previous = t;
+    }
+
+    if let Some(begin) = start {
+        spans.push((begin, 1.0));
+    }
+
+    spans
+}
+
+/// Narrow down where `inside` changes between a and b
+fn bisect(inside: &dyn Fn(f64) -> bool, a: f64, b: f64, steps: usize) -> f64 {
+    let inside_a = inside(a);
+    let (mut a, mut b) = (a, b);
+    for _ in 0..steps {
+        let mid = (a + b) / 2.0;
+        if inside(mid) == inside_a {
+            a = mid;
+        } else {
+            b = mid;
+        }
+    }
+
+    (a + b) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn circle(radius: f64) -> impl Fn(f64, f64) -> f64 {
+        move |x: f64, y: f64| {
+            ((x - 0.5).powi(2) + (y - 0.5).powi(2)).sqrt() - radius
+        }
+    }
+
+    #[test]
+    fn disc() {
+        let outlines = ContourTracer::new(64)
+            .with_refinement(20)
+            .trace(&circle(0.3));
+        assert_eq!(outlines.len(), 1);
+        assert!(outlines[0].is_closed() && outlines[0].is_counterclockwise());
+        assert!((outlines[0].area() - PI * 0.09).abs() < 0.002);
+    }
+
+    #[test]
+    fn ring_has_a_hole() {
+        let outer = circle(0.4);
+        let inner = circle(0.2);
+        let ring = |x: f64, y: f64| outer(x, y).max(-inner(x, y));
+        let outlines = ContourTracer::new(64).trace(&ring);
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines[0].is_counterclockwise());
+        assert!(!outlines[1].is_counterclockwise());
+        assert!(outlines[0].contains(outlines[1].vertices()[0]));
+    }
+
+    #[test]
+    fn trimmed_to_the_square() {
+        let outlines = ContourTracer::new(16).trace(&|_, _| -1.0);
+        assert_eq!(outlines.len(), 1);
+        assert!((outlines[0].area() - 1.0).abs() < 1e-9);
+        assert!(ContourTracer::new(16).trace(&|_, _| 1.0).is_empty());
+    }
+
+    fn assert_spans(found: &[(f64, f64)], expected: &[(f64, f64)]) {
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (&(from, to), &(a, b)) in found.iter().zip(expected) {
+            assert!((from - a).abs() < 1e-6 && (to - b).abs() < 1e-6,
+                "{:?}", found);
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let field = |t: f64| (t - 0.3).abs().min((t - 0.7).abs()) - 0.1;
+        assert_spans(&find_spans(&field, 50), &[(0.2, 0.4), (0.6, 0.8)]);
+        assert_spans(&find_spans(&|t| t - 0.5, 10), &[(0.0, 0.5)]);
+        assert_spans(&find_spans(&|t| 0.25 - t, 10), &[(0.25, 1.0)]);
+        assert!(find_spans(&|_| 1.0, 10).is_empty());
+    }
+}
@@ -0,0 +1,502 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+
+// Stretches of a line shorter than this, in model units, are where it only
+// grazes a corner of the footprint
+const MIN_STRETCH: f64 = 1e-9;
+
+/// The footprint of a height field seen from above, inside the unit square.
+/// Slices are trimmed to where they cross it.
+#[derive(Debug, Clone)]
+pub enum Domain {
+    /// The whole unit square
+    Square,
+    /// An ellipse, or a circle when both radii are the same
+    Ellipse { center: Vec2, radii: Vec2 },
+    /// A polygon with its corners in counterclockwise order
+    Polygon(Vec<Vec2>),
+}
+
+impl Domain {
+    /// The circle that fills the square
+    pub fn circle() -> Self {
+        Self::ellipse(1.0, 1.0)
+    }
+
+    /// The ellipse centered in the square, `width` across along x and
+    /// `depth` along y, as fractions of the square
+    pub fn ellipse(width: f64, depth: f64) -> Self {
+        Self::Ellipse {
+            center: Vec2(0.5, 0.5),
+            radii: Vec2(width / 2.0, depth / 2.0),
+        }
+    }
+
+    /// A polygon with these corners in either order
+    pub fn polygon(corners: &[Vec2]) -> Self {
+        let mut corners = corners.to_vec();
+        if !Polyline::new(&corners, true).is_counterclockwise() {
+            corners.reverse();
+        }
+
+        Self::Polygon(corners)
+    }
+
+    pub fn is_square(&self) -> bool {
+        matches!(self, Self::Square)
+    }
+
+    /// Whether this is a circle, which looks the same from every side
+    pub fn is_circle(&self) -> bool {
+        matches!(self, Self::Ellipse { radii: Vec2(a, b), .. } if a == b)
+    }
+
+    /// Whether a point seen from above is in the footprint. Slices crossing
+    /// right on the edge of a circle, ellipse or polygon would have no
+    /// material around their slits, so its edge is outside. The square
+    /// keeps its edges, which slices only run up to.
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            Self::Square => {
+                let Vec2(x, y) = point;
+                (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)
+            },
+            Self::Ellipse { center, radii } => {
+                let Vec2(x, y) = point.sub(*center);
+                let Vec2(a, b) = *radii;
+                (x / a).powi(2) + (y / b).powi(2) < 1.0
+            },
+            Self::Polygon(corners) => {
+                Polyline::new(corners, true).contains(point) &&
+                    !self.edges().any(|(start, end)| {
+                        on_segment(point, start, end)
+                    })
+            },
+        }
+    }
+
+    /// The stretches of the line from `start` to `end` inside the
+    /// footprint, as fractions of the way along it, in order
+    pub fn stretches(&self, start: Vec2, end: Vec2) -> Vec<(f64, f64)> {
+        let along = end.sub(start);
+        let stretches = match self {
+            Self::Square => {
+                let (mut from, mut to) = (0.0, 1.0);
+                for &(p, d) in [(start.0, along.0), (start.1, along.1)].iter() {
+                    if d == 0.0 {
+                        if !(0.0..=1.0).contains(&p) {
+                            return Vec::new();
+                        }
+                        continue;
+                    }
+
+                    let (a, b) = (-p / d, (1.0 - p) / d);
+                    from = a.min(b).max(from);
+                    to = a.max(b).min(to);
+                }
+                vec![(from, to)]
+            },
+            Self::Ellipse { center, radii } => {
+                // Solve |(p + t d) / r| = 1 for t
+                let Vec2(a, b) = *radii;
+                let Vec2(px, py) = start.sub(*center);
+                let Vec2(dx, dy) = along;
+                let (px, py, dx, dy) = (px / a, py / b, dx / a, dy / b);
+                let qa = dx * dx + dy * dy;
+                let qb = 2.0 * (px * dx + py * dy);
+                let qc = px * px + py * py - 1.0;
+                let discriminant = qb * qb - 4.0 * qa * qc;
+                if qa == 0.0 || discriminant <= 0.0 {
+                    return Vec::new();
+                }
+
+                let root = discriminant.sqrt();
+                let from = (-qb - root) / (2.0 * qa);
+                let to = (-qb + root) / (2.0 * qa);
+                vec![(from.max(0.0), to.min(1.0))]
+            },
+            Self::Polygon(_) => {
+                let mut cuts = vec![0.0, 1.0];
+                for (a, b) in self.edges() {
+                    let edge = b.sub(a);
+                    let denominator = along.cross(edge);
+                    if denominator == 0.0 {
+                        continue;
+                    }
+
+                    let t = a.sub(start).cross(edge) / denominator;
+                    let s = a.sub(start).cross(along) / denominator;
+                    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&s) {
+                        cuts.push(t);
+                    }
+                }
+                cuts.sort_by(f64::total_cmp);
+
+                // Whole stretches between cuts are either in or out
+                let mut stretches: Vec<(f64, f64)> = Vec::new();
+                for pair in cuts.windows(2) {
+                    let (from, to) = (pair[0], pair[1]);
+                    let middle = start.translate(along.scale((from + to) / 2.0));
+                    if to - from < MIN_STRETCH || !self.contains(middle) {
+                        continue;
+                    }
+
+                    match stretches.last_mut() {
+                        Some(last) if from - last.1 < MIN_STRETCH => last.1 = to,
+                        _ => stretches.push((from, to)),
+                    }
+                }
+                stretches
+            },
+        };
+
+        stretches
+            .into_iter()
+            .filter(|(from, to)| (to - from) * along.length() >= MIN_STRETCH)
+            .collect()
+    }
+
+    /// The middle of the footprint
+    pub fn center(&self) -> Vec2 {
+        match self {
+            Self::Square => Vec2(0.5, 0.5),
+            Self::Ellipse { center, .. } => *center,
+            Self::Polygon(corners) => {
+                // Centroid of the area, from triangles fanned out from the
+                // origin
+                let mut area = 0.0;
+                let mut sum = Vec2(0.0, 0.0);
+                for (a, b) in self.edges() {
+                    let weight = a.cross(b);
+                    area += weight;
+                    sum = sum.translate(a.translate(b).scale(weight));
+                }
+                if area == 0.0 {
+                    return corners[0];
+                }
+                sum.scale(1.0 / (3.0 * area))
+            },
+        }
+    }
+
+    /// Whether every point on the edge can be seen in a straight line from
+    /// the center, so the footprint can be drawn as rings around it
+    pub fn is_star_shaped(&self) -> bool {
+        let center = self.center();
+        match self {
+            Self::Polygon(_) => self.edges().all(|(a, b)| {
+                b.sub(a).cross(center.sub(a)) > 0.0
+            }),
+            _ => true,
+        }
+    }
+
+    /// About `samples` points around the edge, counterclockwise. The
+    /// corners of a polygon are always included.
+    pub fn outline(&self, samples: usize) -> Vec<Vec2> {
+        match self {
+            Self::Square => Self::polygon(&[
+                Vec2(0.0, 0.0),
+                Vec2(1.0, 0.0),
+                Vec2(1.0, 1.0),
+                Vec2(0.0, 1.0),
+            ]).outline(samples),
+            Self::Ellipse { center, radii } => {
+                let Vec2(a, b) = *radii;
+                (0..samples)
+                    .map(|i| {
+                        let angle = 2.0 * PI * i as f64 / samples as f64;
+                        center.translate(Vec2(a * angle.cos(), b * angle.sin()))
+                    })
+                    .collect()
+            },
+            Self::Polygon(_) => {
+                let perimeter: f64 = self
+                    .edges()
+                    .map(|(a, b)| b.sub(a).length())
+                    .sum();
+                let mut points = Vec::new();
+                for (a, b) in self.edges() {
+                    let share = b.sub(a).length() / perimeter * samples as f64;
+                    let pieces = (share.round() as usize).max(1);
+                    points.extend((0..pieces).map(|i| {
+                        a.translate(b.sub(a).scale(i as f64 / pieces as f64))
+                    }));
+                }
+                points
+            },
+        }
+    }
+
+    /// Whether any two edges of a polygon meet anywhere other than the
+    /// corner between neighbors
+    fn edges_touch(&self) -> bool {
+        let edges: Vec<(Vec2, Vec2)> = self.edges().collect();
+        let count = edges.len();
+        (0..count).any(|i| (i + 1..count).any(|j| {
+            let ((a, b), (c, d)) = (edges[i], edges[j]);
+            // Neighbors share a corner, so they only meet elsewhere when
+            // one folds back along the other
+            if j == i + 1 {
+                on_segment(d, a, b) || on_segment(a, c, d)
+            } else if i == 0 && j == count - 1 {
+                on_segment(c, a, b) || on_segment(b, c, d)
+            } else {
+                segments_touch(a, b, c, d)
+            }
+        }))
+    }
+
+    /// Each edge of a polygon from corner to corner
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let corners: &[Vec2] = match self {
+            Self::Polygon(corners) => corners,
+            _ => &[],
+        };
+        (0..corners.len())
+            .map(move |i| (corners[i], corners[(i + 1) % corners.len()]))
+    }
+}
+
+impl fmt::Display for Domain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Square => write!(f, "square"),
+            Self::Ellipse { radii: Vec2(a, b), .. } if a == b && *a == 0.5 => {
+                write!(f, "circle")
+            },
+            Self::Ellipse { radii: Vec2(a, b), .. } => {
+                write!(f, "ellipse:{},{}", 2.0 * a, 2.0 * b)
+            },
+            Self::Polygon(corners) => {
+                let corners: Vec<String> = corners
+                    .iter()
+                    .map(|Vec2(x, y)| format!("{},{}", x, y))
+                    .collect();
+                write!(f, "polygon:{}", corners.join(";"))
+            },
+        }
+    }
+}
+
+impl FromStr for Domain {
+    type Err = String;
+
+    /// "square", "circle", an ellipse like "ellipse:1,0.6" with its width
+    /// and depth as fractions of the square, or a polygon like
+    /// "polygon:0.5,0;1,1;0,1" with its corners in the unit square
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = |text: &str, what: &str| {
+            text.split(',')
+                .map(|part| {
+                    part.trim()
+                        .parse::<f64>()
+                        .map_err(|_| format!("'{}' is not {}", part, what))
+                })
+                .collect::<Result<Vec<f64>, String>>()
+        };
+
+        let (kind, spec) = s.split_once(':').unwrap_or((s, ""));
+        match (kind.trim(), spec.trim()) {
+            ("square", "") => Ok(Self::Square),
+            ("circle", "") => Ok(Self::circle()),
+            ("ellipse", spec) => match numbers(spec, "a size")?[..] {
+                [width, depth] if (0.0..=1.0).contains(&width) && width > 0.0 &&
+                        (0.0..=1.0).contains(&depth) && depth > 0.0 => {
+                    Ok(Self::ellipse(width, depth))
+                },
+                [_, _] => Err(String::from(
+                    "an ellipse's width and depth must be from 0 to 1")),
+                _ => Err(String::from(
+                    "an ellipse needs a width and depth, like ellipse:1,0.6")),
+            },
+            ("polygon", spec) => {
+                let corners = spec
+                    .split(';')
+                    .map(|corner| match numbers(corner, "a coordinate")?[..] {
+                        [x, y] if (0.0..=1.0).contains(&x) &&
+                                (0.0..=1.0).contains(&y) => Ok(Vec2(x, y)),
+                        [_, _] => Err(format!(
+                            "corner '{}' is outside the unit square", corner)),
+                        _ => Err(format!("'{}' is not an x,y corner", corner)),
+                    })
+                    .collect::<Result<Vec<Vec2>, String>>()?;
+                if corners.len() < 3 {
+                    return Err(String::from(
+                        "a polygon needs at least 3 corners"));
+                }
+                if Polyline::new(&corners, true).area() == 0.0 {
+                    return Err(String::from("the polygon has no area"));
+                }
+
+                let polygon = Self::polygon(&corners);
+                if polygon.edges_touch() {
+                    return Err(String::from(
+                        "the polygon's edges cross or touch each other"));
+                }
+                // The preview meshes the top as rings around the center
+                if !polygon.is_star_shaped() {
+                    return Err(String::from(
+                        "the whole edge of the polygon must be visible from \
+                        its center"));
+                }
+
+                Ok(polygon)
+            },
+            _ => Err(String::from(
+                "expected square, circle, ellipse:<width>,<depth> or \
+                polygon:<x>,<y>;<x>,<y>;...")),
+        }
+    }
+}
+
+/// Whether `point` lies on the segment from `start` to `end`
+fn on_segment(point: Vec2, start: Vec2, end: Vec2) -> bool {
+    let edge = end.sub(start);
+    let offset = point.sub(start);
+    let t = offset.dot(edge) / edge.dot(edge);
+    edge.cross(offset).abs() <= MIN_STRETCH * edge.length() &&
+        (0.0..=1.0).contains(&t)
+}
+
+/// Whether the segments from `a` to `b` and from `c` to `d` cross or touch
+fn segments_touch(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| q.sub(p).cross(r.sub(p));
+    let crosses = side(a, b, c) * side(a, b, d) < 0.0 &&
+        side(c, d, a) * side(c, d, b) < 0.0;
+    crosses || on_segment(c, a, b) || on_segment(d, a, b) ||
+        on_segment(a, c, d) || on_segment(b, c, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(spec: &str) -> Domain {
+        spec.parse().unwrap()
+    }
+
+    fn assert_stretches(actual: Vec<(f64, f64)>, expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (&(from, to), &(a, b)) in actual.iter().zip(expected) {
+            assert!((from - a).abs() < 1e-9 && (to - b).abs() < 1e-9,
+                "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    const NOTCHED: &str =
+        "polygon:0,0;1,0;1,1;0.6,1;0.6,0.5;0.4,0.5;0.4,1;0,1";
+
+    /// A square with a notch cut into the middle of the top edge. The
+    /// slicer handles it, though the command line doesn't take it.
+    fn notched() -> Domain {
+        let corners: Vec<Vec2> = NOTCHED["polygon:".len()..]
+            .split(';')
+            .map(|corner| {
+                let (x, y) = corner.split_once(',').unwrap();
+                Vec2(x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect();
+        Domain::polygon(&corners)
+    }
+
+    #[test]
+    fn parses_footprints() {
+        assert!(parse("square").is_square());
+        assert!(parse(" circle ").is_circle());
+        assert!(!parse("ellipse:1,0.6").is_circle());
+        assert!(parse("ellipse:0.5,0.5").is_circle());
+        match parse("polygon:0.5,0;1,1;0,1") {
+            Domain::Polygon(corners) => assert_eq!(corners.len(), 3),
+            other => panic!("{} is not a polygon", other),
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for &spec in ["square", "circle", "ellipse:1,0.6",
+                "polygon:0.5,0;1,1;0,1"].iter() {
+            assert_eq!(parse(spec).to_string(), spec);
+        }
+
+        // Clockwise corners are turned around
+        assert_eq!(
+            parse("polygon:0,1;1,1;0.5,0").to_string(),
+            "polygon:0.5,0;1,1;0,1");
+    }
+
+    #[test]
+    fn rejects_bad_footprints() {
+        for &spec in ["", "hexagon", "square:1", "circle:0.5", "ellipse",
+                "ellipse:1", "ellipse:1,0", "ellipse:1.5,1", "ellipse:a,1",
+                "polygon:0,0;1,1", "polygon:0,0;1,0;2,1",
+                "polygon:0,0;1;0,1", "polygon:0,0;0.5,0.5;1,1"].iter() {
+            assert!(spec.parse::<Domain>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn contains() {
+        let square = Domain::Square;
+        assert!(square.contains(Vec2(0.0, 1.0)));
+        assert!(!square.contains(Vec2(1.1, 0.5)));
+
+        let circle = Domain::circle();
+        assert!(circle.contains(Vec2(0.5, 0.5)));
+        assert!(!circle.contains(Vec2(1.0, 0.5)));
+        assert!(!circle.contains(Vec2(0.05, 0.05)));
+
+        let notched = notched();
+        assert!(notched.contains(Vec2(0.2, 0.8)));
+        assert!(!notched.contains(Vec2(0.5, 0.8)));
+        assert!(!notched.contains(Vec2(0.5, 0.5)));
+    }
+
+    #[test]
+    fn stretches() {
+        let (start, end) = (Vec2(-0.5, 0.5), Vec2(1.5, 0.5));
+        assert_stretches(Domain::Square.stretches(start, end), &[(0.25, 0.75)]);
+        assert_stretches(Domain::circle().stretches(start, end), &[(0.25, 0.75)]);
+        assert_stretches(
+            notched().stretches(Vec2(0.0, 0.75), Vec2(1.0, 0.75)),
+            &[(0.0, 0.4), (0.6, 1.0)]);
+
+        // Lines that miss or only graze the footprint
+        let (start, end) = (Vec2(0.0, 1.5), Vec2(1.0, 1.5));
+        assert!(Domain::Square.stretches(start, end).is_empty());
+        assert!(Domain::circle().stretches(start, end).is_empty());
+        let (start, end) = (Vec2(0.0, 1.0), Vec2(1.0, 1.0));
+        assert!(Domain::circle().stretches(start, end).is_empty());
+    }
+
+    #[test]
+    fn centers_and_star_shapes() {
+        let triangle = parse("polygon:0,0;1,0;1,1");
+        let Vec2(x, y) = triangle.center();
+        assert!((x - 2.0 / 3.0).abs() < 1e-9 && (y - 1.0 / 3.0).abs() < 1e-9);
+        assert!(triangle.is_star_shaped());
+        assert!(Domain::circle().is_star_shaped());
+        assert!(!notched().is_star_shaped());
+    }
+
+    #[test]
+    fn rejects_polygons_the_preview_cant_mesh() {
+        let error = |spec: &str| spec.parse::<Domain>().unwrap_err();
+
+        // A star drawn in one stroke, a corner folding back along its
+        // edge, a corner repeated and a corner on another edge
+        for &spec in [
+                "polygon:0.5,1;0.2,0.1;0.98,0.65;0.02,0.65;0.8,0.1",
+                "polygon:0,0;1,0;0.5,0;0.5,1",
+                "polygon:0,0;1,0;1,0;0,1",
+                "polygon:0,0;1,0;1,1;0.5,0;0,1"].iter() {
+            assert!(error(spec).contains("edges cross"), "{}", spec);
+        }
+
+        assert!(error(NOTCHED).contains("visible from its center"));
+    }
+}
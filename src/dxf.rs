@@ -0,0 +1,368 @@
+use std::io::{self, Write};
+
+use crate::backend::{Backend, OpenFile, Style};
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+use crate::units::{Unit, INCH};
+
+/// Text in output units. `position` is the left end of the baseline and
+/// `angle` is in degrees counterclockwise.
+struct Label {
+    text: String,
+    position: Vec2,
+    height: f64,
+    angle: f64,
+}
+
+/// A slice in output units along with where to insert it
+struct Block {
+    polylines: Vec<(Polyline, Style)>,
+    labels: Vec<Label>,
+    position: Vec2,
+}
+
+/// Either everything goes into one file, or each slice gets its own.
+pub enum DxfOutput<W: Write> {
+    Single(W),
+    PerSlice(OpenFile<W>),
+}
+
+/// Writes ASCII DXF (AutoCAD R12) files for CAD/CAM tools. Every slice
+/// becomes a block with its outline on the CUT layer and its slits on the
+/// SLIT layer. In a single file, blocks are inserted where the page layout
+/// puts them with pages side by side from left to right. Otherwise each
+/// slice is inserted at the origin of its own file.
+///
+/// Blocks must come before the entities that insert them, so everything is
+/// buffered until `finish()`.
+pub struct DxfBackend<W: Write> {
+    output: DxfOutput<W>,
+    unit: Unit,
+    blocks: Vec<Block>,
+    // Local origin of the current slice and left edge of the current page,
+    // both in points
+    slice_offset: Vec2,
+    page_offset: f64,
+    page_width: f64,
+}
+
+impl<W: Write> DxfBackend<W> {
+    pub const CUT_LAYER: &'static str = "CUT";
+    pub const SLIT_LAYER: &'static str = "SLIT";
+    pub const BBOX_LAYER: &'static str = "BBOX";
+    pub const LABEL_LAYER: &'static str = "LABEL";
+
+    // Gap between pages when laid out side by side, in points
+    const PAGE_GAP: f64 = 0.5 * INCH;
+
+    pub fn new(output: DxfOutput<W>, unit: Unit) -> Self {
+        Self {
+            output,
+            unit,
+            blocks: Vec::new(),
+            slice_offset: Vec2(0.0, 0.0),
+            page_offset: 0.0,
+            page_width: 0.0,
+        }
+    }
+
+    fn layer(style: Style) -> &'static str {
+        match style {
+            Style::Outline => Self::CUT_LAYER,
+            Style::Slit => Self::SLIT_LAYER,
+            Style::BoundingBox => Self::BBOX_LAYER,
+            Style::Label => Self::LABEL_LAYER,
+        }
+    }
+
+    fn block_name(index: usize) -> String {
+        format!("SLICE{}", index + 1)
+    }
+
+    fn write_header(file: &mut W) -> io::Result<()> {
+        writeln!(file, "0\nSECTION\n2\nHEADER")?;
+        writeln!(file, "9\n$ACADVER\n1\nAC1009")?;
+        writeln!(file, "0\nENDSEC")
+    }
+
+    fn write_tables(file: &mut W) -> io::Result<()> {
+        writeln!(file, "0\nSECTION\n2\nTABLES")?;
+
+        writeln!(file, "0\nTABLE\n2\nLTYPE\n70\n1")?;
+        writeln!(file, "0\nLTYPE\n2\nCONTINUOUS\n70\n0\n3\nSolid line")?;
+        writeln!(file, "72\n65\n73\n0\n40\n0.0")?;
+        writeln!(file, "0\nENDTAB")?;
+
+        // Colors are AutoCAD color indices: 7 is black/white, 1 is red,
+        // 8 is gray, 5 is blue
+        let layers = [
+            (Self::CUT_LAYER, 7),
+            (Self::SLIT_LAYER, 1),
+            (Self::BBOX_LAYER, 8),
+            (Self::LABEL_LAYER, 5),
+        ];
+        writeln!(file, "0\nTABLE\n2\nLAYER\n70\n{}", layers.len())?;
+        for (layer, color) in layers.iter() {
+            writeln!(file, "0\nLAYER\n2\n{}\n70\n0", layer)?;
+            writeln!(file, "62\n{}\n6\nCONTINUOUS", color)?;
+        }
+        writeln!(file, "0\nENDTAB")?;
+
+        writeln!(file, "0\nENDSEC")
+    }
+
+    /// Write an R12 POLYLINE entity with one VERTEX per point.
+    fn write_polyline(file: &mut W, polyline: &Polyline, layer: &str)
+            -> io::Result<()> {
+        writeln!(file, "0\nPOLYLINE\n8\n{}\n66\n1\n70\n0", layer)?;
+        writeln!(file, "10\n0.0\n20\n0.0\n30\n0.0")?;
+        for Vec2(x, y) in polyline.vertices().iter() {
+            writeln!(file, "0\nVERTEX\n8\n{}", layer)?;
+            writeln!(file, "10\n{}\n20\n{}\n30\n0.0", x, y)?;
+        }
+        writeln!(file, "0\nSEQEND\n8\n{}", layer)
+    }
+
+    fn write_block(file: &mut W, name: &str, block: &Block)
+            -> io::Result<()> {
+        writeln!(file, "0\nBLOCK\n8\n0\n2\n{}\n70\n0", name)?;
+        writeln!(file, "10\n0.0\n20\n0.0\n30\n0.0\n3\n{}", name)?;
+
+        for (polyline, style) in block.polylines.iter() {
+            Self::write_polyline(file, polyline, Self::layer(*style))?;
+        }
+
+        for label in block.labels.iter() {
+            let Vec2(x, y) = label.position;
+            writeln!(file, "0\nTEXT\n8\n{}", Self::LABEL_LAYER)?;
+            writeln!(file, "10\n{}\n20\n{}\n30\n0.0", x, y)?;
+            writeln!(file, "40\n{}\n1\n{}", label.height, label.text)?;
+            if label.angle != 0.0 {
+                writeln!(file, "50\n{}", label.angle)?;
+            }
+        }
+
+        writeln!(file, "0\nENDBLK\n8\n0")
+    }
+
+    fn write_insert(file: &mut W, name: &str, position: Vec2)
+            -> io::Result<()> {
+        let Vec2(x, y) = position;
+        writeln!(file, "0\nINSERT\n8\n0\n2\n{}", name)?;
+        writeln!(file, "10\n{}\n20\n{}\n30\n0.0", x, y)
+    }
+
+    fn write_file(file: &mut W, blocks: &[&Block], at_origin: bool)
+            -> io::Result<()> {
+        Self::write_header(file)?;
+        Self::write_tables(file)?;
+
+        writeln!(file, "0\nSECTION\n2\nBLOCKS")?;
+        for (i, block) in blocks.iter().enumerate() {
+            Self::write_block(file, &Self::block_name(i), block)?;
+        }
+        writeln!(file, "0\nENDSEC")?;
+
+        writeln!(file, "0\nSECTION\n2\nENTITIES")?;
+        for (i, block) in blocks.iter().enumerate() {
+            let position = if at_origin {
+                Vec2(0.0, 0.0)
+            } else {
+                block.position
+            };
+            Self::write_insert(file, &Self::block_name(i), position)?;
+        }
+        writeln!(file, "0\nENDSEC")?;
+
+        writeln!(file, "0\nEOF")?;
+        file.flush()
+    }
+}
+
+impl<W: Write> Backend for DxfBackend<W> {
+    fn begin_page(&mut self, width: f64, _height: f64) -> io::Result<()> {
+        if !self.blocks.is_empty() {
+            self.page_offset += self.page_width + Self::PAGE_GAP;
+        }
+        self.page_width = width;
+
+        Ok(())
+    }
+
+    fn begin_slice(&mut self, offset: Vec2) -> io::Result<()> {
+        self.slice_offset = offset;
+        let position = offset
+            .translate(Vec2(self.page_offset, 0.0))
+            .scale(1.0 / self.unit.points());
+        self.blocks.push(Block {
+            polylines: Vec::new(),
+            labels: Vec::new(),
+            position,
+        });
+
+        Ok(())
+    }
+
+    fn draw_polyline(&mut self, polyline: &Polyline, style: Style)
+            -> io::Result<()> {
+        // Don't write individual points.
+        if polyline.vertices().len() < 2 {
+            return Ok(());
+        }
+
+        // Blocks are stored relative to the slice's own origin
+        let local = polyline
+            .translate(self.slice_offset.scale(-1.0))
+            .scale(1.0 / self.unit.points());
+
+        let block = self.blocks
+            .last_mut()
+            .expect("polylines must be drawn between begin/end_slice");
+        block.polylines.push((local, style));
+
+        Ok(())
+    }
+
+    fn draw_text(&mut self, text: &str, position: Vec2, size: f64, angle: f64)
+            -> io::Result<()> {
+        let factor = 1.0 / self.unit.points();
+        let local = position.sub(self.slice_offset).scale(factor);
+
+        let block = self.blocks
+            .last_mut()
+            .expect("labels must be drawn between begin/end_slice");
+        block.labels.push(Label {
+            text: text.to_string(),
+            position: local,
+            height: size * factor,
+            angle,
+        });
+
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let blocks = std::mem::take(&mut self.blocks);
+        match &mut self.output {
+            DxfOutput::Single(file) => {
+                let all: Vec<&Block> = blocks.iter().collect();
+                Self::write_file(file, &all, false)
+            },
+            DxfOutput::PerSlice(open_file) => {
+                for (i, block) in blocks.iter().enumerate() {
+                    let mut file = open_file(i + 1)?;
+                    Self::write_file(&mut file, &[block], true)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::{draw_piece, MemoryFiles};
+
+    fn render(unit: Unit) -> String {
+        let mut out = Vec::new();
+        draw_piece(&mut DxfBackend::new(DxfOutput::Single(&mut out), unit))
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Group codes and their values
+    fn pairs(dxf: &str) -> Vec<(&str, &str)> {
+        let lines: Vec<&str> = dxf.lines().collect();
+        assert_eq!(lines.len() % 2, 0);
+        lines.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    /// The layer of every entity of this type in order
+    fn layers<'a>(pairs: &[(&str, &'a str)], entity: &str) -> Vec<&'a str> {
+        pairs
+            .windows(2)
+            .filter(|w| w[0] == ("0", entity) && w[1].0 == "8")
+            .map(|w| w[1].1)
+            .collect()
+    }
+
+    #[test]
+    fn sections_in_order() {
+        let dxf = render(Unit::Inches);
+        let pairs = pairs(&dxf);
+        let sections: Vec<&str> = pairs
+            .windows(2)
+            .filter(|w| w[0] == ("0", "SECTION"))
+            .map(|w| w[1].1)
+            .collect();
+        assert_eq!(sections, ["HEADER", "TABLES", "BLOCKS", "ENTITIES"]);
+        assert_eq!(pairs.last(), Some(&("0", "EOF")));
+        for layer in ["CUT", "SLIT", "BBOX", "LABEL"].iter() {
+            assert!(dxf.contains(&format!("0\nLAYER\n2\n{}\n", layer)));
+        }
+    }
+
+    #[test]
+    fn slices_are_blocks_on_layers() {
+        let dxf = render(Unit::Inches);
+        let pairs = pairs(&dxf);
+        assert!(dxf.contains("0\nBLOCK\n8\n0\n2\nSLICE1\n"));
+        assert_eq!(layers(&pairs, "POLYLINE"), ["CUT", "SLIT", "LABEL"]);
+        assert_eq!(layers(&pairs, "TEXT"), ["LABEL"]);
+        assert!(dxf.contains("1\nX1\n"));
+
+        // Blocks are in inches from the slice's own origin
+        let outline: Vec<&str> = pairs
+            .iter()
+            .skip_while(|&&pair| pair != ("0", "POLYLINE"))
+            .take_while(|&&pair| pair != ("0", "SEQEND"))
+            .filter(|pair| pair.0 == "10" || pair.0 == "20")
+            .map(|pair| pair.1)
+            .collect();
+        assert_eq!(outline, [
+            "0.0", "0.0", // the polyline's own origin
+            "0", "0", "1", "0", "1", "1", "0", "1", "0", "0",
+        ]);
+        assert!(dxf.contains("0\nINSERT\n8\n0\n2\nSLICE1\n10\n1\n20\n1\n"));
+    }
+
+    #[test]
+    fn millimeters() {
+        let dxf = render(Unit::Millimeters);
+        assert!(dxf.contains("2\nSLICE1\n10\n25.4\n20\n25.4\n"));
+        assert!(dxf.contains("40\n3.5277777777777777\n1\nX1\n"));
+    }
+
+    #[test]
+    fn one_file_per_slice() {
+        let files = MemoryFiles::default();
+        let output = DxfOutput::PerSlice(files.opener());
+        let mut backend = DxfBackend::new(output, Unit::Inches);
+        let square = Polyline::new(&[
+            Vec2(0.0, 0.0),
+            Vec2(72.0, 0.0),
+            Vec2(72.0, 72.0),
+        ], true);
+        backend.begin_page(612.0, 792.0).unwrap();
+        for &offset in [Vec2(0.0, 0.0), Vec2(144.0, 0.0)].iter() {
+            backend.begin_slice(offset).unwrap();
+            backend.draw_polyline(&square.translate(offset), Style::Outline)
+                .unwrap();
+            backend.end_slice().unwrap();
+        }
+        backend.end_page().unwrap();
+        backend.finish().unwrap();
+
+        let contents = files.contents();
+        assert_eq!(contents.len(), 2);
+        for dxf in contents.iter() {
+            assert!(dxf.contains("0\nINSERT\n8\n0\n2\nSLICE1\n10\n0\n20\n0\n"));
+            assert_eq!(layers(&pairs(dxf), "POLYLINE"), ["CUT"]);
+        }
+    }
+}
@@ -0,0 +1,633 @@
+use std::f64::consts::{E, PI, TAU};
+use std::fmt;
+
+use crate::geom::Vec3;
+use crate::heights::{Height1D, Height2D};
+use crate::solids::Solid;
+
+/// A parse error at a 1-based column of the expression
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Variable {
+    X,
+    Y,
+    Z,
+    /// Distance from the center, 1 at the middle of each edge
+    R,
+    /// Angle around the center in radians
+    Theta,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x" => Some(Self::X),
+            "y" => Some(Self::Y),
+            "z" => Some(Self::Z),
+            "r" => Some(Self::R),
+            "theta" => Some(Self::Theta),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Pow,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        use Function::*;
+        let function = match name {
+            "sin" => Sin,
+            "cos" => Cos,
+            "tan" => Tan,
+            "asin" => Asin,
+            "acos" => Acos,
+            "atan" => Atan,
+            "atan2" => Atan2,
+            "sinh" => Sinh,
+            "cosh" => Cosh,
+            "tanh" => Tanh,
+            "exp" => Exp,
+            "ln" => Ln,
+            "sqrt" => Sqrt,
+            "abs" => Abs,
+            "floor" => Floor,
+            "ceil" => Ceil,
+            "pow" => Pow,
+            "min" => Min,
+            "max" => Max,
+            "clamp" => Clamp,
+            _ => return None,
+        };
+
+        Some(function)
+    }
+
+    /// The smallest and largest number of arguments
+    fn arity(&self) -> (usize, usize) {
+        use Function::*;
+        match self {
+            Atan2 | Pow => (2, 2),
+            Min | Max => (2, usize::MAX),
+            Clamp => (3, 3),
+            _ => (1, 1),
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        use Function::*;
+        match self {
+            Sin => args[0].sin(),
+            Cos => args[0].cos(),
+            Tan => args[0].tan(),
+            Asin => args[0].asin(),
+            Acos => args[0].acos(),
+            Atan => args[0].atan(),
+            Atan2 => args[0].atan2(args[1]),
+            Sinh => args[0].sinh(),
+            Cosh => args[0].cosh(),
+            Tanh => args[0].tanh(),
+            Exp => args[0].exp(),
+            Ln => args[0].ln(),
+            Sqrt => args[0].sqrt(),
+            Abs => args[0].abs(),
+            Floor => args[0].floor(),
+            Ceil => args[0].ceil(),
+            Pow => args[0].powf(args[1]),
+            Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Clamp => args[0].max(args[1]).min(args[2]),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+/// Expressions are parsed into a tree with every name already resolved, so
+/// evaluating never has to look anything up.
+#[derive(Debug)]
+enum Node {
+    Number(f64),
+    Variable(Variable),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    fn eval(&self, vars: &Vars) -> f64 {
+        match self {
+            Self::Number(x) => *x,
+            Self::Variable(var) => vars.get(*var),
+            Self::Negate(node) => -node.eval(vars),
+            Self::Binary(op, a, b) => {
+                let a = a.eval(vars);
+                let b = b.eval(vars);
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Subtract => a - b,
+                    BinaryOp::Multiply => a * b,
+                    BinaryOp::Divide => a / b,
+                    BinaryOp::Power => a.powf(b),
+                }
+            },
+            Self::Call(function, args) => {
+                let values: Vec<f64> = args.iter().map(|arg| arg.eval(vars)).collect();
+                function.apply(&values)
+            },
+        }
+    }
+}
+
+struct Vars {
+    x: f64,
+    y: f64,
+    z: f64,
+    r: f64,
+    theta: f64,
+}
+
+impl Vars {
+    fn get(&self, var: Variable) -> f64 {
+        match var {
+            Variable::X => self.x,
+            Variable::Y => self.y,
+            Variable::Z => self.z,
+            Variable::R => self.r,
+            Variable::Theta => self.theta,
+        }
+    }
+}
+
+/// A formula typed in by the user, like `exp(-4 * r^2) * cos(6 * theta)`.
+///
+/// Expressions support `+ - * / ^`, parentheses, the constants `pi`, `tau`
+/// and `e`, and the functions sin, cos, tan, asin, acos, atan, atan2, sinh,
+/// cosh, tanh, exp, ln, sqrt, abs, floor, ceil, pow, min, max and clamp.
+///
+/// Surfaces can use `x` and `y` in [0, 1], and `r` and `theta`, the polar
+/// coordinates around the center of the square. `r` is 1 at the middle of
+/// each edge. Profiles only have `x`. Solids have `x`, `y` and `z` and are
+/// inside wherever the formula is negative.
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Parse `z = f(x, y)`
+    pub fn surface(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text, &[Variable::X, Variable::Y, Variable::R, Variable::Theta])
+    }
+
+    /// Parse the field of a solid `f(x, y, z)`
+    pub fn solid(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text, &[Variable::X, Variable::Y, Variable::Z])
+    }
+
+    /// Parse a 1D profile `f(x)`
+    pub fn profile(text: &str) -> Result<Self, ParseError> {
+        Self::parse(text, &[Variable::X])
+    }
+
+    fn parse(text: &str, variables: &[Variable]) -> Result<Self, ParseError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            variables,
+            end_column: text.chars().count() + 1,
+        };
+
+        let root = parser.parse_sum()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.column, "expected an operator"));
+        }
+
+        Ok(Self { root })
+    }
+}
+
+impl Height2D for Expression {
+    fn compute(&self, x: f64, y: f64) -> f64 {
+        let (cx, cy) = (2.0 * x - 1.0, 2.0 * y - 1.0);
+        let vars = Vars {
+            x,
+            y,
+            z: 0.0,
+            r: (cx * cx + cy * cy).sqrt(),
+            theta: cy.atan2(cx),
+        };
+
+        self.root.eval(&vars)
+    }
+}
+
+impl Height1D for Expression {
+    fn compute(&self, x: f64) -> f64 {
+        let vars = Vars {
+            x,
+            y: 0.0,
+            z: 0.0,
+            r: 0.0,
+            theta: 0.0,
+        };
+
+        self.root.eval(&vars)
+    }
+}
+
+impl Solid for Expression {
+    fn field(&self, p: Vec3) -> f64 {
+        let Vec3(x, y, z) = p;
+        let vars = Vars {
+            x,
+            y,
+            z,
+            r: 0.0,
+            theta: 0.0,
+        };
+
+        self.root.eval(&vars)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Exponent, as in 1.5e-3
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+
+            let number: String = chars[start..i].iter().collect();
+            let value = number.parse().map_err(|_| ParseError {
+                message: format!("invalid number '{}'", number),
+                column,
+            })?;
+            tokens.push(Token { kind: TokenKind::Number(value), column });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name = chars[start..i].iter().collect();
+            tokens.push(Token { kind: TokenKind::Name(name), column });
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token { kind: TokenKind::Symbol(c), column });
+            i += 1;
+        } else {
+            return Err(ParseError {
+                message: format!("unexpected character '{}'", c),
+                column,
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent, loosest binding first:
+///
+/// ```text
+/// sum     = product (("+" | "-") product)*
+/// product = unary (("*" | "/") unary)*
+/// unary   = "-" unary | power
+/// power   = atom ("^" unary)?
+/// atom    = number | name | name "(" sum ("," sum)* ")" | "(" sum ")"
+/// ```
+///
+/// `^` is right associative and binds tighter than unary minus, so
+/// `-x^2` is `-(x^2)`.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a [Variable],
+    // Errors at the end of the input point just past the last character
+    end_column: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn column(&self) -> usize {
+        self.peek().map_or(self.end_column, |token| token.column)
+    }
+
+    fn error_at(&self, column: usize, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            column,
+        }
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        self.error_at(self.column(), message)
+    }
+
+    fn next_symbol_is(&self, symbol: char) -> bool {
+        self.peek().is_some_and(|token| token.kind == TokenKind::Symbol(symbol))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        if self.next_symbol_is(symbol) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.parse_product()?;
+        loop {
+            let op = if self.next_symbol_is('+') {
+                BinaryOp::Add
+            } else if self.next_symbol_is('-') {
+                BinaryOp::Subtract
+            } else {
+                return Ok(node);
+            };
+            self.pos += 1;
+
+            let rhs = self.parse_product()?;
+            node = Node::Binary(op, Box::new(node), Box::new(rhs));
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.parse_unary()?;
+        loop {
+            let op = if self.next_symbol_is('*') {
+                BinaryOp::Multiply
+            } else if self.next_symbol_is('/') {
+                BinaryOp::Divide
+            } else {
+                return Ok(node);
+            };
+            self.pos += 1;
+
+            let rhs = self.parse_unary()?;
+            node = Node::Binary(op, Box::new(node), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, ParseError> {
+        if self.next_symbol_is('-') {
+            self.pos += 1;
+            let node = self.parse_unary()?;
+            Ok(Node::Negate(Box::new(node)))
+        } else {
+            self.parse_power()
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Node, ParseError> {
+        let base = self.parse_atom()?;
+        if self.next_symbol_is('^') {
+            self.pos += 1;
+            let exponent = self.parse_unary()?;
+            Ok(Node::Binary(BinaryOp::Power, Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, ParseError> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error("unexpected end of expression")),
+        };
+
+        match token.kind {
+            TokenKind::Number(value) => {
+                self.pos += 1;
+                Ok(Node::Number(value))
+            },
+            TokenKind::Symbol('(') => {
+                self.pos += 1;
+                let node = self.parse_sum()?;
+                self.expect_symbol(')')?;
+                Ok(node)
+            },
+            TokenKind::Name(name) => {
+                self.pos += 1;
+                if self.next_symbol_is('(') {
+                    self.parse_call(&name, token.column)
+                } else {
+                    self.parse_name(&name, token.column)
+                }
+            },
+            TokenKind::Symbol(_) => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_name(&self, name: &str, column: usize) -> Result<Node, ParseError> {
+        let constant = match name {
+            "pi" => Some(PI),
+            "tau" => Some(TAU),
+            "e" => Some(E),
+            _ => None,
+        };
+        if let Some(value) = constant {
+            return Ok(Node::Number(value));
+        }
+
+        match Variable::from_name(name) {
+            Some(var) if self.variables.contains(&var) => Ok(Node::Variable(var)),
+            Some(_) => Err(self.error_at(
+                column,
+                &format!("'{}' can't be used here, only {}", name, self.variable_names()))),
+            None if Function::from_name(name).is_some() => Err(self.error_at(
+                column,
+                &format!("function '{}' needs arguments in parentheses", name))),
+            None => Err(self.error_at(column, &format!("unknown name '{}'", name))),
+        }
+    }
+
+    fn variable_names(&self) -> String {
+        let names: Vec<&str> = self.variables
+            .iter()
+            .map(|var| match var {
+                Variable::X => "x",
+                Variable::Y => "y",
+                Variable::Z => "z",
+                Variable::R => "r",
+                Variable::Theta => "theta",
+            })
+            .collect();
+
+        names.join(", ")
+    }
+
+    fn parse_call(&mut self, name: &str, column: usize) -> Result<Node, ParseError> {
+        let function = Function::from_name(name).ok_or_else(
+            || self.error_at(column, &format!("unknown function '{}'", name)))?;
+
+        self.expect_symbol('(')?;
+        let mut args = vec![self.parse_sum()?];
+        while self.next_symbol_is(',') {
+            self.pos += 1;
+            args.push(self.parse_sum()?);
+        }
+        self.expect_symbol(')')?;
+
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            let expected = if min == max {
+                format!("{}", min)
+            } else {
+                format!("at least {}", min)
+            };
+            let plural = if expected == "1" { "" } else { "s" };
+            return Err(self.error_at(column, &format!(
+                "'{}' takes {} argument{}, found {}",
+                name,
+                expected,
+                plural,
+                args.len())));
+        }
+
+        Ok(Node::Call(function, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface(text: &str, x: f64, y: f64) -> f64 {
+        Height2D::compute(&Expression::surface(text).unwrap(), x, y)
+    }
+
+    fn profile(text: &str, x: f64) -> f64 {
+        Height1D::compute(&Expression::profile(text).unwrap(), x)
+    }
+
+    fn error(result: Result<Expression, ParseError>) -> (usize, String) {
+        let err = result.err().expect("expected a parse error");
+        (err.column, err.message)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(profile("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(profile("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(profile("2 ^ 3 ^ 2", 0.0), 512.0);
+        assert_eq!(profile("-2 ^ 2", 0.0), -4.0);
+        assert_eq!(profile("2 ^ -1", 0.0), 0.5);
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        assert_eq!(profile("x * x", 0.5), 0.25);
+        assert_eq!(surface("max(x, y, 0.1)", 0.2, 0.7), 0.7);
+        assert_eq!(surface("clamp(x - y, 0, 1)", 0.2, 0.7), 0.0);
+        assert!((surface("r", 1.0, 0.5) - 1.0).abs() < 1e-12);
+        assert!((surface("theta", 0.5, 1.0) - PI / 2.0).abs() < 1e-12);
+        assert!((profile("cos(pi)", 0.0) + 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn solid_has_z() {
+        let solid = Expression::solid("x + y + z").unwrap();
+        assert_eq!(solid.field(Vec3(0.25, 0.5, 1.0)), 1.75);
+    }
+
+    #[test]
+    fn no_value_is_nan() {
+        assert!(profile("sqrt(x - 0.5)", 0.0).is_nan());
+        let expression = Expression::surface("sqrt(x - 0.5)").unwrap();
+        assert_eq!(expression.compute_clamped(0.0, 0.0), 0.0);
+        assert_eq!(expression.compute_clamped(1.0, 0.0), 0.5_f64.sqrt());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(Expression::profile("1 +")).0, 4);
+        assert_eq!(error(Expression::profile("(1")).1, "expected ')'");
+        assert_eq!(error(Expression::profile("1 2")).1, "expected an operator");
+        assert_eq!(error(Expression::profile("foo")).1, "unknown name 'foo'");
+        assert_eq!(error(Expression::profile("y")),
+            (1, String::from("'y' can't be used here, only x")));
+        assert_eq!(error(Expression::profile("sin")).1,
+            "function 'sin' needs arguments in parentheses");
+        assert_eq!(error(Expression::profile("x + atan2(x)")),
+            (5, String::from("'atan2' takes 2 arguments, found 1")));
+        assert!(Expression::profile("1 $ 2").is_err());
+    }
+}
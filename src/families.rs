@@ -0,0 +1,204 @@
+use crate::geom::Vec2;
+
+// Points closer than this where different pairs of slices cross are the
+// same point
+const SAME_POINT: f64 = 1e-9;
+
+/// A family of parallel slices, seen from above as lines across the unit
+/// square
+pub struct Family {
+    direction: Vec2,
+    positions: Vec<f64>,
+}
+
+impl Family {
+    /// Slices running at `angle` radians counterclockwise from the x-axis.
+    /// Positions go from 0 to 1 across the square, from one side to the
+    /// other, whatever the angle.
+    pub fn new(angle: f64, positions: Vec<f64>) -> Self {
+        Self {
+            direction: Vec2(angle.cos(), angle.sin()),
+            positions,
+        }
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        self.direction
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Where slice `index` enters the square and how far it runs across
+    pub fn line(&self, index: usize) -> (Vec2, f64) {
+        let Vec2(dx, dy) = self.direction;
+        let normal = self.direction.rotate_quarter();
+        let extent = dx.abs() + dy.abs();
+        let offset = (self.positions[index] - 0.5) * extent;
+        let through = Vec2(0.5, 0.5).translate(normal.scale(offset));
+
+        // Where the line is between both pairs of edges
+        let Vec2(px, py) = through;
+        let mut start = f64::NEG_INFINITY;
+        let mut end = f64::INFINITY;
+        for &(p, d) in [(px, dx), (py, dy)].iter() {
+            if d.abs() < SAME_POINT {
+                continue;
+            }
+
+            let (a, b) = (-p / d, (1.0 - p) / d);
+            start = start.max(a.min(b));
+            end = end.min(a.max(b));
+        }
+
+        (through.translate(self.direction.scale(start)), end - start)
+    }
+}
+
+/// A point seen from above where two or more slices cross
+pub struct Junction {
+    pub point: Vec2,
+    /// The family and index of each slice, in order of family
+    pub slices: Vec<(usize, usize)>,
+}
+
+/// Every point in the square where slices of different families cross.
+/// Slices from three families can all cross at the same point, and then
+/// they make one junction.
+pub fn junctions(families: &[Family]) -> Vec<Junction> {
+    let mut junctions: Vec<Junction> = Vec::new();
+    for (a, first) in families.iter().enumerate() {
+        for (b, second) in families.iter().enumerate().skip(a + 1) {
+            let denominator = first.direction.cross(second.direction);
+            if denominator.abs() < SAME_POINT {
+                continue;
+            }
+
+            for i in 0..first.len() {
+                for j in 0..second.len() {
+                    let (p, _) = first.line(i);
+                    let (q, _) = second.line(j);
+                    let s = q.sub(p).cross(second.direction) / denominator;
+                    let point = p.translate(first.direction.scale(s));
+                    // Slices that meet right at the edge of the square
+                    // only touch corners
+                    let Vec2(x, y) = point;
+                    let inside = SAME_POINT..1.0 - SAME_POINT;
+                    if !inside.contains(&x) || !inside.contains(&y) {
+                        continue;
+                    }
+
+                    let slices = [(a, i), (b, j)];
+                    let existing = junctions.iter_mut().find(|junction| {
+                        junction.point.sub(point).length() < SAME_POINT
+                    });
+                    match existing {
+                        Some(junction) => {
+                            for slice in slices.iter() {
+                                if !junction.slices.contains(slice) {
+                                    junction.slices.push(*slice);
+                                }
+                            }
+                        },
+                        None => junctions.push(Junction {
+                            point,
+                            slices: slices.to_vec(),
+                        }),
+                    }
+                }
+            }
+        }
+    }
+
+    // Pairs are found in order of the first family, so a third family can
+    // be added out of order
+    for junction in junctions.iter_mut() {
+        junction.slices.sort_unstable();
+    }
+
+    junctions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(actual.sub(expected).length() < 1e-9,
+            "{:?} != {:?}", (actual.0, actual.1), (expected.0, expected.1));
+    }
+
+    #[test]
+    fn lines_across_the_square() {
+        let family = Family::new(0.0, vec![0.25]);
+        let (start, length) = family.line(0);
+        assert_close(start, Vec2(0.0, 0.25));
+        assert!((length - 1.0).abs() < 1e-9);
+
+        // Diagonal positions reach from corner to corner
+        let family = Family::new(FRAC_PI_4, vec![0.0, 0.5, 1.0]);
+        let (start, length) = family.line(1);
+        assert_close(start, Vec2(0.0, 0.0));
+        assert!((length - SQRT_2).abs() < 1e-9);
+        for &index in [0, 2].iter() {
+            assert!(family.line(index).1.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn grid_junctions() {
+        let families = [
+            Family::new(0.0, vec![0.25, 0.75]),
+            Family::new(FRAC_PI_2, vec![0.25, 0.75]),
+        ];
+        let found = junctions(&families);
+        assert_eq!(found.len(), 4);
+        for junction in found.iter() {
+            assert_eq!(junction.slices.len(), 2);
+            assert_eq!(junction.slices[0].0, 0);
+            assert_eq!(junction.slices[1].0, 1);
+        }
+    }
+
+    #[test]
+    fn three_families_share_junctions() {
+        let families = [
+            Family::new(0.0, vec![0.25, 0.75]),
+            Family::new(FRAC_PI_4, vec![0.5]),
+            Family::new(FRAC_PI_2, vec![0.25, 0.75]),
+        ];
+        let found = junctions(&families);
+        assert_eq!(found.len(), 4);
+        let shared: Vec<&Junction> = found
+            .iter()
+            .filter(|junction| junction.slices.len() == 3)
+            .collect();
+        assert_eq!(shared.len(), 2);
+        for junction in shared {
+            let families: Vec<usize> =
+                junction.slices.iter().map(|&(family, _)| family).collect();
+            assert_eq!(families, vec![0, 1, 2]);
+            let Vec2(x, y) = junction.point;
+            assert!((x - y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn no_junctions() {
+        // Parallel slices never cross
+        let parallel = [
+            Family::new(0.0, vec![0.5]),
+            Family::new(0.0, vec![0.25]),
+        ];
+        assert!(junctions(&parallel).is_empty());
+
+        // Slices that only meet on the edge of the square don't count
+        let edges = [
+            Family::new(0.0, vec![0.0]),
+            Family::new(FRAC_PI_2, vec![0.0, 1.0]),
+        ];
+        assert!(junctions(&edges).is_empty());
+    }
+}
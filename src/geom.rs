@@ -16,6 +16,51 @@ impl Vec2 {
 
         Self(x * factor, y * factor)
     }
+
+    pub fn sub(&self, other: Self) -> Self {
+        let Self(x1, y1) = self;
+        let Self(x2, y2) = other;
+
+        Self(x1 - x2, y1 - y2)
+    }
+
+    pub fn dot(&self, other: Self) -> f64 {
+        let Self(x1, y1) = self;
+        let Self(x2, y2) = other;
+
+        x1 * x2 + y1 * y2
+    }
+
+    /// z-component of the 3D cross product. Positive when `other` is
+    /// counterclockwise from `self`.
+    pub fn cross(&self, other: Self) -> f64 {
+        let Self(x1, y1) = self;
+        let Self(x2, y2) = other;
+
+        x1 * y2 - y1 * x2
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        self.scale(1.0 / self.length())
+    }
+
+    /// Rotate 90 degrees clockwise
+    pub fn perpendicular(&self) -> Self {
+        let Self(x, y) = self;
+
+        Self(*y, -x)
+    }
+
+    /// Rotate 90 degrees counterclockwise
+    pub fn rotate_quarter(&self) -> Self {
+        let Self(x, y) = self;
+
+        Self(-y, *x)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -55,7 +100,7 @@ impl Vec3 {
     }
 }
 
-impl<'a, 'b> Sub<&'b Vec3> for &'a Vec3 {
+impl<'b> Sub<&'b Vec3> for &Vec3 {
     type Output = Vec3;
 
     fn sub(self, other: &'b Vec3) -> Vec3 {
@@ -1,14 +1,14 @@
 pub trait Height2D {
     fn compute(&self, x: f64, y: f64) -> f64;
 
+    /// The height from 0 to 1. A formula can give NaN where it has no
+    /// value, like sqrt(x - 0.5), and the surface has no height there.
     fn compute_clamped(&self, x: f64, y: f64) -> f64 {
-        let val = self.compute(x, y);
-        if val < 0.0 {
+        let height = self.compute(x, y);
+        if height.is_nan() {
             0.0
-        } else if val > 1.0 {
-            1.0
         } else {
-            val
+            height.clamp(0.0, 1.0)
         }
     }
 }
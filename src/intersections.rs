@@ -0,0 +1,125 @@
+/// A stretch of material where an X slice and a Y slice cross. The X slice
+/// gets a slit from the top down to `split` and the Y slice one from the
+/// bottom up to it, so the two slide together.
+pub struct Crossing {
+    /// Index of the X slice and of the Y slice
+    pub x_slice: usize,
+    pub y_slice: usize,
+    /// Where the slices cross, seen from above
+    pub x: f64,
+    pub y: f64,
+    pub bottom: f64,
+    pub top: f64,
+    pub split: f64,
+}
+
+impl Crossing {
+    /// The stretch of material the slices cross in
+    pub fn span(&self) -> (f64, f64) {
+        (self.bottom, self.top)
+    }
+}
+
+/// Every crossing between the slices. Each one is found once and both
+/// mating slits are made from it, so they always agree.
+pub struct Intersections {
+    crossings: Vec<Crossing>,
+}
+
+impl Intersections {
+    /// Find the crossings of the X slices at `x_positions` along y and the
+    /// Y slices at `y_positions` along x. `spans(x, y)` gives the stretches
+    /// of material on the vertical line through (x, y), from the bottom up.
+    /// The slices meet `split_ratio` of the way up each stretch. Stretches
+    /// shorter than `min_height` are too thin to hold the slices together,
+    /// so they get no slits at all.
+    pub fn new(
+            x_positions: &[f64],
+            y_positions: &[f64],
+            spans: impl Fn(f64, f64) -> Vec<(f64, f64)>,
+            split_ratio: f64,
+            min_height: f64) -> Self {
+        let mut crossings = Vec::new();
+        for (x_slice, &y) in x_positions.iter().enumerate() {
+            for (y_slice, &x) in y_positions.iter().enumerate() {
+                for (bottom, top) in spans(x, y) {
+                    if top - bottom < min_height {
+                        continue;
+                    }
+
+                    crossings.push(Crossing {
+                        x_slice,
+                        y_slice,
+                        x,
+                        y,
+                        bottom,
+                        top,
+                        split: bottom + split_ratio * (top - bottom),
+                    });
+                }
+            }
+        }
+
+        Self { crossings }
+    }
+
+    pub fn along_x_slice(&self, index: usize) -> impl Iterator<Item = &Crossing> {
+        self.crossings.iter().filter(move |c| c.x_slice == index)
+    }
+
+    pub fn along_y_slice(&self, index: usize) -> impl Iterator<Item = &Crossing> {
+        self.crossings.iter().filter(move |c| c.y_slice == index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_crossing_per_span() {
+        // Two stretches of material where x < 0.5, one where it is more
+        let spans = |x: f64, _: f64| {
+            if x < 0.5 {
+                vec![(0.0, 0.2), (0.4, 1.0)]
+            } else {
+                vec![(0.0, 0.5)]
+            }
+        };
+        let intersections = Intersections::new(
+            &[0.25, 0.75], &[0.25, 0.75], spans, 0.5, 0.0);
+
+        let along_x: Vec<(usize, f64, f64)> = intersections
+            .along_x_slice(1)
+            .map(|c| (c.y_slice, c.x, c.split))
+            .collect();
+        assert_eq!(along_x, vec![(0, 0.25, 0.1), (0, 0.25, 0.7), (1, 0.75, 0.25)]);
+
+        let along_y: Vec<(usize, f64)> = intersections
+            .along_y_slice(0)
+            .map(|c| (c.x_slice, c.y))
+            .collect();
+        assert_eq!(along_y, vec![(0, 0.25), (0, 0.25), (1, 0.75), (1, 0.75)]);
+    }
+
+    #[test]
+    fn split_ratio() {
+        let intersections = Intersections::new(
+            &[0.5], &[0.5], |_, _| vec![(0.0, 2.0)], 0.25, 0.0);
+        let crossing = intersections.along_x_slice(0).next().unwrap();
+        assert_eq!(crossing.span(), (0.0, 2.0));
+        assert_eq!(crossing.split, 0.5);
+    }
+
+    #[test]
+    fn thin_spans_get_no_slits() {
+        let intersections = Intersections::new(
+            &[0.5], &[0.5], |_, _| vec![(0.0, 0.01), (0.5, 1.0)], 0.5, 0.1);
+        let spans: Vec<(f64, f64)> = intersections
+            .along_x_slice(0)
+            .map(Crossing::span)
+            .collect();
+        assert_eq!(spans, vec![(0.5, 1.0)]);
+        assert_eq!(intersections.along_y_slice(1).count(), 0);
+    }
+}
@@ -0,0 +1,330 @@
+use std::fmt;
+
+/// A parsed JSON document. Objects keep their keys in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Name of the JSON type, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "a boolean",
+            Self::Number(_) => "a number",
+            Self::String(_) => "a string",
+            Self::Array(_) => "an array",
+            Self::Object(_) => "an object",
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Self::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected text after the end of the document"));
+    }
+
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ParseError {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+
+        ParseError {
+            message: message.to_string(),
+            line,
+            column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Value::String(self.parse_string()?)),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if c.is_alphabetic() => self.parse_literal(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut fields: Vec<(String, Value)> = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key_start = self.pos;
+            let key = self.parse_string()?;
+            if fields.iter().any(|(existing, _)| *existing == key) {
+                self.pos = key_start;
+                return Err(self.error(&format!("duplicate key '{}'", key)));
+            }
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            fields.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                },
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                },
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(result),
+                '\\' => result.push(self.parse_escape()?),
+                c if c.is_control() => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                },
+                c => result.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, ParseError> {
+        let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+        self.pos += 1;
+        let escaped = match c {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let hex: String = self.chars
+                    .iter()
+                    .skip(self.pos)
+                    .take(4)
+                    .collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .ok_or_else(|| self.error("expected 4 hex digits"))?;
+                self.pos += 4;
+                // Surrogate pairs aren't needed for model files
+                char::from_u32(code)
+                    .ok_or_else(|| self.error("unsupported unicode escape"))?
+            },
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("unknown escape sequence"));
+            },
+        };
+
+        Ok(escaped)
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(
+                |c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+            self.pos += 1;
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(Value::Number(x)),
+            _ => {
+                self.pos = start;
+                Err(self.error(&format!("invalid number '{}'", text)))
+            },
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric()) {
+            self.pos += 1;
+        }
+
+        let word: String = self.chars[start..self.pos].iter().collect();
+        match word.as_str() {
+            "null" => Ok(Value::Null),
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => {
+                self.pos = start;
+                Err(self.error(&format!("unexpected '{}'", word)))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (usize, usize, String) {
+        let err = parse(text).expect_err("expected a parse error");
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn values() {
+        let value = parse(r#"{"a": [1, -2.5e1, true, null], "b": "x\né"}"#)
+            .unwrap();
+        assert_eq!(value, Value::Object(vec![
+            (String::from("a"), Value::Array(vec![
+                Value::Number(1.0),
+                Value::Number(-25.0),
+                Value::Bool(true),
+                Value::Null,
+            ])),
+            (String::from("b"), Value::String(String::from("x\né"))),
+        ]));
+    }
+
+    #[test]
+    fn empty_containers() {
+        assert_eq!(parse(" [ ] ").unwrap(), Value::Array(Vec::new()));
+        assert_eq!(parse("{}").unwrap().as_object(), Some(&[][..]));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error("[1,\n  2,\n  x]"),
+            (3, 3, String::from("unexpected 'x'")));
+        assert_eq!(error(r#"{"a": 1, "a": 2}"#),
+            (1, 10, String::from("duplicate key 'a'")));
+        assert_eq!(error("[1 2]").2, "expected ',' or ']'");
+        assert_eq!(error(r#"{"a" 1}"#).2, "expected ':'");
+        assert_eq!(error(r#""abc"#).2, "unterminated string");
+        assert_eq!(error(r#""\q""#).2, "unknown escape sequence");
+        assert_eq!(error(r#""\u12""#).2, "expected 4 hex digits");
+        assert_eq!(error("1e999").2, "invalid number '1e999'");
+        assert_eq!(error("1 2").2,
+            "unexpected text after the end of the document");
+        assert_eq!(error("").2, "unexpected end of file");
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::geom::Vec2;
+use crate::packing::Packer;
+use crate::page::PageFormat;
+use crate::polylines::{Join, Polyline, Slice, SliceId};
+use crate::slicer::Slicer;
+use crate::units::INCH;
+use crate::validation::{self, Issue, Limits};
+
+/// A slice scaled to points along with where it goes on the page. The
+/// slice keeps its own orientation; `rotated` turns it a quarter turn
+/// counterclockwise before it is moved by `offset`.
+pub struct Placement {
+    pub slice: Slice,
+    pub rotated: bool,
+    pub offset: Vec2,
+}
+
+impl Placement {
+    pub fn placed_slice(&self) -> Slice {
+        if self.rotated {
+            self.slice.rotate_quarter().translate(self.offset)
+        } else {
+            self.slice.translate(self.offset)
+        }
+    }
+
+    /// Move a point from the slice's own coordinates onto the page
+    pub fn to_page(&self, point: Vec2) -> Vec2 {
+        let point = if self.rotated { point.rotate_quarter() } else { point };
+        point.translate(self.offset)
+    }
+
+    pub fn polyline_to_page(&self, polyline: &Polyline) -> Polyline {
+        let points: Vec<Vec2> = polyline.vertices()
+            .iter()
+            .map(|v| self.to_page(*v))
+            .collect();
+        Polyline::new(&points, false)
+    }
+
+    /// Counterclockwise rotation of the slice on the page in degrees
+    pub fn angle(&self) -> f64 {
+        if self.rotated { 90.0 } else { 0.0 }
+    }
+}
+
+pub struct Page {
+    pub placements: Vec<Placement>,
+}
+
+/// How well the slices filled the paper
+pub struct LayoutReport {
+    /// Number of pages, including any with an oversized piece
+    pub pages: usize,
+    /// Fraction of each page's printable area covered by pieces. Pages with
+    /// an oversized piece are left out, since it spills off the page.
+    pub utilization: Vec<f64>,
+    /// Slices too big for the printable area, even when rotated
+    pub oversized: Vec<SliceId>,
+    /// Fragile spots on the pieces
+    pub issues: Vec<Issue>,
+}
+
+impl LayoutReport {
+    fn new(pages: &[Page], page_format: &PageFormat,
+            oversized: Vec<SliceId>, issues: Vec<Issue>) -> Self {
+        let printable_area =
+            page_format.printable_width() * page_format.printable_height();
+        let utilization = pages.iter()
+            .filter(|page| {
+                // Oversized pieces always get a page to themselves
+                !page.placements
+                    .iter()
+                    .any(|placement| oversized.contains(&placement.slice.id()))
+            })
+            .map(|page| {
+                let used: f64 = page.placements
+                    .iter()
+                    .map(|placement| placement.slice.area())
+                    .sum();
+                used / printable_area
+            })
+            .collect();
+
+        Self {
+            pages: pages.len(),
+            utilization,
+            oversized,
+            issues,
+        }
+    }
+
+    /// Utilization over all pages together
+    pub fn total_utilization(&self) -> f64 {
+        let count = self.utilization.len().max(1) as f64;
+        self.utilization.iter().sum::<f64>() / count
+    }
+}
+
+impl fmt::Display for LayoutReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pages: Vec<String> = self.utilization
+            .iter()
+            .map(|u| format!("{:.1}%", 100.0 * u))
+            .collect();
+        let plural = if self.pages == 1 { "" } else { "s" };
+        write!(f, "{} page{}", self.pages, plural)?;
+        if !pages.is_empty() {
+            write!(
+                f,
+                ", {:.1}% of the paper used ({})",
+                100.0 * self.total_utilization(),
+                pages.join(", "))?;
+        }
+
+        let oversized_pages = self.pages - pages.len();
+        if oversized_pages > 0 {
+            write!(f, ", {} with an oversized piece", oversized_pages)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Packs slices onto as few pages as possible. All output formats share this
+/// so the templates come out the same no matter the format.
+pub struct Layout {
+    page_format: PageFormat,
+    scale: f64,
+    // Kerf and spacing in points
+    kerf: f64,
+    join: Join,
+    spacing: f64,
+    allow_rotation: bool,
+    limits: Option<Limits>,
+}
+
+impl Layout {
+    pub fn new(scale: f64, page_format: PageFormat) -> Self {
+        Self {
+            page_format,
+            scale,
+            kerf: 0.0,
+            join: Join::Miter,
+            spacing: 0.0,
+            allow_rotation: false,
+            limits: None,
+        }
+    }
+
+    /// Compensate every slice for a cutter that removes `kerf` points of
+    /// material.
+    pub fn with_kerf(mut self, kerf: f64, join: Join) -> Self {
+        self.kerf = kerf;
+        self.join = join;
+        self
+    }
+
+    /// Leave `spacing` points between pieces
+    pub fn with_spacing(mut self, spacing: f64) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Let pieces turn a quarter turn to fit better
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Check the pieces for fragile spots, if there are limits
+    pub fn with_limits(mut self, limits: Option<Limits>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Pack the x slices and y slices together. Every bounding box is known
+    /// up front so the packer can make the most of each page.
+    pub fn lay_out_slices(self, slicer: &Slicer) -> (Vec<Page>, LayoutReport) {
+        let scaled: Vec<Slice> = slicer.make_slices()
+            .iter()
+            .map(|slice| slice.scale(self.scale * INCH))
+            .collect();
+
+        // Check the pieces as they will come out, before allowing for kerf
+        let issues = match &self.limits {
+            Some(limits) => scaled
+                .iter()
+                .flat_map(|slice| validation::check_slice(slice, limits))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let slices: Vec<Slice> = scaled
+            .into_iter()
+            .map(|slice| self.compensate_kerf(slice))
+            .collect();
+
+        let sizes: Vec<(f64, f64)> = slices.iter()
+            .map(|slice| {
+                let bbox = slice.get_bbox();
+                (bbox.width(), bbox.height())
+            })
+            .collect();
+
+        let page = &self.page_format;
+        let packed = Packer::new(page.printable_width(), page.printable_height())
+            .with_spacing(self.spacing)
+            .with_rotation(self.allow_rotation)
+            .pack(&sizes);
+
+        let page_count = packed.iter().map(|p| p.page + 1).max().unwrap_or(0);
+        let mut pages: Vec<Page> = (0..page_count)
+            .map(|_| Page { placements: Vec::new() })
+            .collect();
+        let mut oversized = Vec::new();
+        let origin = Vec2(page.margins.left, page.margins.bottom);
+        for (slice, spot) in slices.into_iter().zip(packed.iter()) {
+            if spot.oversized {
+                oversized.push(slice.id());
+            }
+
+            // Line up the bottom left corner of the (possibly rotated)
+            // bounding box with the spot the packer chose
+            let bbox = if spot.rotated {
+                slice.rotate_quarter().get_bbox()
+            } else {
+                slice.get_bbox()
+            };
+            let corner = origin.translate(Vec2(spot.x, spot.y));
+            pages[spot.page].placements.push(Placement {
+                slice,
+                rotated: spot.rotated,
+                offset: corner.sub(bbox.bottom_left()),
+            });
+        }
+
+        let report = LayoutReport::new(&pages, page, oversized, issues);
+        (pages, report)
+    }
+
+    fn compensate_kerf(&self, slice: Slice) -> Slice {
+        if self.kerf > 0.0 {
+            slice.compensate_kerf(self.kerf, self.join)
+        } else {
+            slice
+        }
+    }
+}
+
+/// For formats that write several files, slicetest.svg becomes
+/// slicetest-1.svg, slicetest-2.svg, ...
+pub fn numbered_fname(fname: &str, number: usize, extension: &str) -> String {
+    let path = Path::new(fname);
+    let stem = path.file_stem()
+        .map_or(String::from("slicetest"), |s| s.to_string_lossy().into());
+    let numbered = format!("{}-{}.{}", stem, number, extension);
+
+    path.with_file_name(numbered).to_string_lossy().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polylines::Axis;
+
+    fn page(pieces: &[(usize, f64)]) -> Page {
+        let placements = pieces
+            .iter()
+            .map(|&(index, size)| {
+                let outline = Polyline::new(&[
+                    Vec2(0.0, 0.0),
+                    Vec2(size, 0.0),
+                    Vec2(size, size),
+                    Vec2(0.0, size),
+                ], true);
+                let id = SliceId::new(Axis::X, index);
+                Placement {
+                    slice: Slice::new(id, vec![outline], Vec::new()),
+                    rotated: false,
+                    offset: Vec2(0.0, 0.0),
+                }
+            })
+            .collect();
+
+        Page { placements }
+    }
+
+    #[test]
+    fn utilization_leaves_out_oversized_pieces() {
+        // Letter paper with 1/2 inch margins prints 540 x 720 points
+        let pages = [page(&[(0, 270.0), (1, 270.0)]), page(&[(2, 1e9)])];
+        let report = LayoutReport::new(
+            &pages,
+            &PageFormat::default(),
+            vec![SliceId::new(Axis::X, 2)],
+            Vec::new());
+
+        assert_eq!(report.pages, 2);
+        assert_eq!(report.utilization, vec![0.375]);
+        assert_eq!(
+            report.to_string(),
+            "2 pages, 37.5% of the paper used (37.5%), \
+            1 with an oversized piece");
+    }
+
+    #[test]
+    fn only_oversized_pieces() {
+        let report = LayoutReport::new(
+            &[page(&[(0, 1e9)])],
+            &PageFormat::default(),
+            vec![SliceId::new(Axis::X, 0)],
+            Vec::new());
+
+        assert_eq!(report.to_string(), "1 page, 1 with an oversized piece");
+    }
+
+    #[test]
+    fn numbered_file_names() {
+        assert_eq!(numbered_fname("out/a.svg", 2, "svg"), "out/a-2.svg");
+        assert_eq!(numbered_fname("a", 1, "dxf"), "a-1.dxf");
+    }
+}
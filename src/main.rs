@@ -1,5 +1,9 @@
 mod polylines;
 mod polynomial;
+mod profiles;
+mod json;
+mod expression;
+mod registry;
 mod printer;
 mod slicer;
 mod surfaces;
@@ -8,30 +12,231 @@ mod models;
 mod preview;
 mod geom;
 mod mesh;
+mod cli;
+mod layout;
+mod svg;
+mod units;
+mod dxf;
+mod pdf;
+mod backend;
+mod postscript;
+mod page;
+mod packing;
+mod solids;
+mod contours;
+mod positions;
+mod intersections;
+mod validation;
+mod families;
+mod domain;
+mod remap;
 
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::process;
+
+use cli::{Command, TemplateOptions, PreviewOptions, OutputFormat};
+use backend::Backend;
 use printer::Printer;
+use postscript::PostScriptBackend;
+use pdf::PdfBackend;
+use svg::SvgBackend;
+use dxf::{DxfBackend, DxfOutput};
 use slicer::Slicer;
-use preview::SurfacePreview;
+use preview::{FootprintPreview, SurfacePreview};
+use models::{Model, ModelInfo};
+use registry::Registry;
+use solids::Shape;
+use polylines::SliceId;
+use validation::Severity;
+use families::Family;
+use positions::SlicePositions;
 
 fn main() {
-    const MODEL: &str = "nine_tines";
-    const SLICE_RES: u32 = 2;
-    const CURVE_RES: u32 = 6;
-    const SLICE_WIDTH_INCHES: f64 = 2.5;
-    const DRAW_BBOXES: bool = false;
-    const PS_FNAME: &str = "slicetest.ps";
-    const M: usize = 100;
-    const N: usize = 100;
-    const P: usize = 10;
-    const OBJ_FILE: &str = "preview.obj";
-
-    let surf = models::select_model(MODEL);
-    let slicer = Slicer::new(SLICE_RES, CURVE_RES, surf);
-    let mut printer = Printer::new(PS_FNAME, SLICE_WIDTH_INCHES, DRAW_BBOXES);
-    printer.print_slices(&slicer);
-
-    let surf = models::select_model(MODEL);
-    let mut previewer = SurfacePreview::new(M, N, P, surf);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let registry = Registry::bundled();
+    let command = match cli::parse_args(&args, &registry) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            eprintln!("run 'sliceform-surfaces help' for usage");
+            process::exit(2);
+        }
+    };
+
+    let result = match command {
+        Command::Template(options, model) => make_template(&options, model),
+        Command::Preview(options, model) => make_preview(&options, model),
+        Command::ListModels(search) => {
+            list_models(&registry, search.as_deref());
+            Ok(())
+        },
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        },
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn list_models(registry: &Registry, search: Option<&str>) {
+    let found: Vec<&ModelInfo> = match search {
+        Some(query) => registry.search(query),
+        None => registry.models().collect(),
+    };
+    if found.is_empty() {
+        println!("no models match '{}'", search.unwrap_or(""));
+    }
+
+    for info in found {
+        println!("{}", info.name);
+        println!("    {}", info.description);
+        println!(
+            "    defaults: --slice-res {} --curve-res {} --width {}",
+            info.slice_res,
+            info.curve_res,
+            info.width);
+        if !info.domain.is_square() {
+            println!("    domain: {}", info.domain);
+        }
+    }
+}
+
+fn make_template(options: &TemplateOptions, model: Model) -> io::Result<()> {
+    let shape = match model.shape {
+        Shape::HeightField(surface) => {
+            Shape::HeightField(options.heights.apply(surface))
+        },
+        solid => solid,
+    };
+    // Slices are 1 unit wide in the slicer and `width` inches on paper
+    let scale = options.width * units::INCH;
+    let mut slicer = Slicer::new(options.slice_res, options.curve_res, shape)
+        .with_material(options.thickness / scale, options.clearance / scale)
+        .with_split_ratio(options.split)
+        .with_size(
+            options.depth / options.width,
+            options.height / options.width);
+    if let Some(positions) = &options.x_slices {
+        slicer = slicer.with_x_slices(positions.clone());
+    }
+    if let Some(positions) = &options.y_slices {
+        slicer = slicer.with_y_slices(positions.clone());
+    }
+    if !options.angles.is_empty() {
+        let positions = options.x_slices.clone().unwrap_or_else(|| {
+            SlicePositions::from_resolution(options.slice_res).positions()
+        });
+        let families = options.angles
+            .iter()
+            .map(|angle| Family::new(angle.to_radians(), positions.clone()))
+            .collect();
+        slicer = slicer.with_families(families);
+    }
+    if let Some(count) = options.radial {
+        slicer = slicer.with_radial(count, options.rings.clone());
+    }
+    if !options.domain.is_square() {
+        slicer = slicer.with_domain(options.domain.clone());
+    }
+    if let Some(tolerance) = options.tolerance {
+        slicer = slicer.with_tolerance(tolerance / scale);
+    }
+    if options.base > 0.0 {
+        slicer = slicer.with_base(options.base / scale);
+    }
+    if options.base_plate {
+        slicer = slicer.with_base_plate(options.thickness / scale);
+    }
+    let fname = &options.output;
+
+    match options.format {
+        OutputFormat::PostScript => {
+            let file = BufWriter::new(File::create(fname)?);
+            print(PostScriptBackend::new(file), &slicer, options)
+        },
+        OutputFormat::Pdf => {
+            let file = BufWriter::new(File::create(fname)?);
+            print(PdfBackend::new(file), &slicer, options)
+        },
+        OutputFormat::Svg => {
+            let open_file = backend::numbered_files(fname, "svg");
+            print(SvgBackend::new(open_file, options.units), &slicer, options)
+        },
+        OutputFormat::Dxf => {
+            let output = if options.per_slice {
+                DxfOutput::PerSlice(backend::numbered_files(fname, "dxf"))
+            } else {
+                DxfOutput::Single(BufWriter::new(File::create(fname)?))
+            };
+            print(DxfBackend::new(output, options.units), &slicer, options)
+        },
+    }
+}
+
+fn print<B: Backend>(backend: B, slicer: &Slicer, options: &TemplateOptions)
+        -> io::Result<()> {
+    let mut printer = Printer::new(backend, options.width, options.draw_bboxes)
+        .with_page_format(options.page_format)
+        .with_kerf(options.kerf, options.kerf_join)
+        .with_packing(options.spacing, options.rotate)
+        .with_labels(options.draw_labels)
+        .with_limits(options.limits);
+    let report = printer.print_slices(slicer)?;
+
+    for id in report.oversized.iter() {
+        eprintln!("warning: slice {} is larger than the printable area", id);
+    }
+    for issue in report.issues.iter() {
+        let level = match issue.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        eprintln!("{}: {}", level, issue.describe(options.units, &options.limits));
+    }
+    println!("{}", report);
+
+    let mut broken: Vec<SliceId> = report.issues
+        .iter()
+        .filter(|issue| issue.severity() == Severity::Error)
+        .map(|issue| issue.slice)
+        .collect();
+    broken.dedup();
+    if !broken.is_empty() {
+        return Err(io::Error::other(format!(
+            "{} of the pieces would fall apart, see above", broken.len())));
+    }
+
+    Ok(())
+}
+
+fn make_preview(options: &PreviewOptions, model: Model) -> io::Result<()> {
+    let Shape::HeightField(surf) = model.shape else {
+        unreachable!("preview models are checked to be height fields");
+    };
+    let surf = options.heights.apply(surf);
+    // The mesh is 1 unit wide, like the slices
+    let depth = options.depth / options.width;
+    let height = options.height / options.width;
+    if !options.domain.is_square() {
+        let mut previewer = FootprintPreview::new(
+            options.domain.clone(),
+            options.samples_x,
+            options.samples_y,
+            options.samples_z,
+            surf)
+            .with_size(depth, height);
+        previewer.generate_mesh();
+        return previewer.save_obj_file(&options.output);
+    }
+
+    let mut previewer = SurfacePreview::new(
+        options.samples_x, options.samples_y, options.samples_z, surf)
+        .with_size(depth, height);
     previewer.generate_mesh();
-    previewer.save_obj_file(OBJ_FILE);
+    previewer.save_obj_file(&options.output)
 }
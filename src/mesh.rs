@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
 
 use crate::geom::{Vec3, Triangle};
 
@@ -20,14 +20,14 @@ impl Face {
         }
     }
 
-    pub fn write_line(&self, file: &mut File) {
+    pub fn write_line(&self, file: &mut File) -> io::Result<()> {
         writeln!(
             file, 
             "f {0}//{3} {1}//{3} {2}//{3}", 
             self.v1 + 1, 
             self.v2 + 1, 
             self.v3 + 1, 
-            self.normal + 1).expect("Failed to write face");
+            self.normal + 1)
     }
 }
 
@@ -77,21 +77,21 @@ impl Mesh {
         self.add_face(face);
     }
     
-    pub fn save_obj_file(&self, fname: &str) {
-        let mut file = File::create(fname).expect("Failed to open file");
+    pub fn save_obj_file(&self, fname: &str) -> io::Result<()> {
+        let mut file = File::create(fname)?;
         
         for Vec3(x, y, z) in self.vertices.iter() {
-            writeln!(&mut file, "v {} {} {}", x, y, z)
-                .expect("Failed to write vertex");
+            writeln!(&mut file, "v {} {} {}", x, y, z)?;
         }
 
         for Vec3(x, y, z) in self.normals.iter() {
-            writeln!(&mut file, "vn {} {} {}", x, y, z)
-                .expect("Failed to write normal");
+            writeln!(&mut file, "vn {} {} {}", x, y, z)?;
         }
 
         for face in self.faces.iter() {
-            face.write_line(&mut file);
+            face.write_line(&mut file)?;
         }
+
+        Ok(())
     }
 }
@@ -1,156 +1,619 @@
-use crate::heights::Height2D;
-use crate::surfaces::{
-    SurfaceOfRevolution, 
-    DistanceMetric, 
-    SineHill, 
-    HeightFunction,
-    ProductSurface,
-};
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::domain::Domain;
+use crate::expression::{self, Expression};
+use crate::geom::Vec3;
+use crate::heights::{Height1D, Height2D};
+use crate::json::{self, ParseError, Value};
 use crate::polynomial::Polynomial;
+use crate::profiles::{Combine, Gaussian, Peak, Peaks, PiecewiseLinear, Sine};
+use crate::registry::Registry;
+use crate::solids::{Combination, Operation, Shape, Solid, Sphere, Torus};
+use crate::surfaces::{DistanceMetric, ProductSurface, SurfaceOfRevolution};
+
+// Settings for models that don't recommend their own
+pub const DEFAULT_SLICE_RES: u32 = 2;
+pub const DEFAULT_CURVE_RES: u32 = 6;
+pub const DEFAULT_WIDTH: f64 = 2.5;
+
+const CUBE_CENTER: Vec3 = Vec3(0.5, 0.5, 0.5);
+
+// Keys every model file may have besides the ones for its type
+const METADATA_KEYS: [&str; 5] =
+    ["description", "slice_res", "curve_res", "width", "domain"];
 
-fn crater_hill() -> SurfaceOfRevolution {
-    let poly = Polynomial::new(vec![0.5, -1.4, 6.7, -5.5]);
-    SurfaceOfRevolution::new(Box::new(poly), DistanceMetric::Euclidean)
+/// What a model says about itself besides the surface
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub name: String,
+    pub description: String,
+    pub slice_res: u32,
+    pub curve_res: u32,
+    /// Recommended print width in inches
+    pub width: f64,
+    /// Footprint of a height field seen from above
+    pub domain: Domain,
+    /// Whether the surface is the same all the way around the center, so
+    /// every slice through the center has the same profile
+    pub round: bool,
 }
 
-fn crater_diamond() -> SurfaceOfRevolution {
-    let poly = Polynomial::new(vec![0.5, -1.4, 6.7, -5.5]);
-    SurfaceOfRevolution::new(Box::new(poly), DistanceMetric::Manhattan)
+impl ModelInfo {
+    fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            slice_res: DEFAULT_SLICE_RES,
+            curve_res: DEFAULT_CURVE_RES,
+            width: DEFAULT_WIDTH,
+            domain: Domain::Square,
+            round: false,
+        }
+    }
+}
+
+pub struct Model {
+    pub info: ModelInfo,
+    pub shape: Shape,
 }
 
-fn step_hill() -> SurfaceOfRevolution {
-    let step_func = HeightFunction::new(steps);
-    SurfaceOfRevolution::new(Box::new(step_func), DistanceMetric::Euclidean)
+/// Where the surface to slice comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelSource {
+    /// A bundled model or a .json model file
+    Named(String),
+    /// A formula for z = f(x, y)
+    Expression(String),
 }
 
-fn sinc_box() -> SurfaceOfRevolution {
-    let sinc_func = HeightFunction::new(sinc);
-    SurfaceOfRevolution::new(Box::new(sinc_func), DistanceMetric::Chessboard)
+impl ModelSource {
+    /// Look names up in `registry`, unless they are paths to .json files
+    pub fn load(&self, registry: &Registry) -> Result<Model, ModelError> {
+        match self {
+            Self::Named(path) if path.ends_with(".json") => {
+                let text = fs::read_to_string(path).map_err(|err| ModelError::Io {
+                    path: path.clone(),
+                    err,
+                })?;
+                parse_model(path, &text)
+            },
+            Self::Named(name) => registry.construct(name),
+            Self::Expression(text) => {
+                let expression = Expression::surface(text)
+                    .map_err(|err| ModelError::Expression {
+                        text: text.clone(),
+                        err,
+                    })?;
+                Ok(Model {
+                    info: ModelInfo::new("expression", text),
+                    shape: Shape::HeightField(Box::new(expression)),
+                })
+            },
+        }
+    }
 }
 
-fn peak_rings() -> SurfaceOfRevolution {
-    let peak_func = HeightFunction::new(double_peak);
-    SurfaceOfRevolution::new(Box::new(peak_func), DistanceMetric::Chessboard)
+#[derive(Debug)]
+pub enum ModelError {
+    /// Not in the registry, with the closest name if there is one
+    Unknown { name: String, suggestion: Option<String> },
+    Expression { text: String, err: expression::ParseError },
+    Io { path: String, err: io::Error },
+    Syntax { source: String, err: ParseError },
+    Invalid { source: String, reason: String },
 }
 
-fn hill(x: f64) -> f64 {
-    let y = x - 0.5;
-    1.0 - 3.0 * y * y
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown { name, suggestion: Some(suggestion) } => write!(
+                f, "unknown model '{}', did you mean '{}'?", name, suggestion),
+            Self::Unknown { name, suggestion: None } => write!(
+                f,
+                "unknown model '{}', run 'sliceform-surfaces list-models' \
+                to see them all",
+                name),
+            Self::Expression { text, err } => write!(
+                f, "invalid expression '{}': {}", text, err),
+            Self::Io { path, err } => write!(f, "can't read {}: {}", path, err),
+            Self::Syntax { source, err } => write!(f, "{}: {}", source, err),
+            Self::Invalid { source, reason } => write!(f, "{}: {}", source, reason),
+        }
+    }
 }
 
-fn peak_thing() -> ProductSurface {
-    let peak_func = double_peak;
-    let hill_func = hill;
+/// Build a model from the text of a model file. `name` identifies the
+/// model and names the file in error messages.
+///
+/// A model is a height field, either a surface of revolution, a product of
+/// two profiles, or a formula:
+///
+/// ```text
+/// { "type": "revolution", "metric": "euclidean", "profile": <profile> }
+/// { "type": "product", "x": <profile>, "y": <profile> }
+/// { "type": "expression", "formula": "x * y" }
+/// ```
+///
+/// or a solid in the unit cube:
+///
+/// ```text
+/// { "type": "sphere", "center": [0.5, 0.5, 0.5], "radius": 0.4 }
+/// { "type": "torus", "center": [0.5, 0.5, 0.5], "major_radius": 0.3,
+///   "minor_radius": 0.15 }
+/// { "type": "implicit", "formula": "x^2 + y^2 - z" }
+/// { "type": "union" | "intersection" | "difference", "solids": [<solid>...] }
+/// ```
+///
+/// Profiles are 1D functions with a "type" of polynomial, piecewise_linear,
+/// peaks, sine, gaussian, or expression. See the files in models/ for
+/// examples. Any model may also have a "description" and recommended
+/// "slice_res", "curve_res" and print "width" in inches. A height field may
+/// have a "domain" to stand on instead of the unit square, like "circle" or
+/// "polygon:0.5,0;1,1;0,1".
+pub fn parse_model(name: &str, text: &str) -> Result<Model, ModelError> {
+    let value = json::parse(text).map_err(|err| ModelError::Syntax {
+        source: name.to_string(),
+        err,
+    })?;
+
+    let invalid = |reason| ModelError::Invalid {
+        source: name.to_string(),
+        reason,
+    };
+    let mut info = make_info(&value, name).map_err(invalid)?;
+    let shape = make_shape(&value).map_err(invalid)?;
+    if matches!(shape, Shape::Solid(_)) && !info.domain.is_square() {
+        return Err(invalid(String::from(
+            "model.domain: only height fields have a domain, not solids")));
+    }
+    info.round = is_round(&value);
 
-    ProductSurface::new(peak_func, hill_func)
+    Ok(Model { info, shape })
 }
 
-fn quad_peak() -> ProductSurface {
-    ProductSurface::new(double_peak, double_peak)
+fn make_info(value: &Value, name: &str) -> Result<ModelInfo, String> {
+    let fields = Fields::new(value, "model")?;
+    let mut info = ModelInfo::new(name, fields.string_or("description", "")?);
+    if fields.get("slice_res").is_some() {
+        info.slice_res = fields.count("slice_res")?;
+    }
+    if fields.get("curve_res").is_some() {
+        info.curve_res = fields.count("curve_res")?;
+    }
+    info.width = fields.number_or("width", DEFAULT_WIDTH)?;
+    if info.width <= 0.0 {
+        return Err(String::from("model.width: must be positive"));
+    }
+    if fields.get("domain").is_some() {
+        info.domain = fields
+            .string("domain")?
+            .parse()
+            .map_err(|err| format!("model.domain: {}", err))?;
+    }
+
+    Ok(info)
 }
 
-fn nine_peak() -> ProductSurface {
-    ProductSurface::new(triple_peak, triple_peak)
+/// Surfaces of revolution measured in straight lines from the center are
+/// round. Other metrics make squares or diamonds.
+fn is_round(value: &Value) -> bool {
+    Fields::new(value, "model").is_ok_and(|fields| {
+        fields.string("type") == Ok("revolution") &&
+            fields.string_or("metric", "euclidean") == Ok("euclidean")
+    })
 }
 
-fn nine_tines() -> ProductSurface {
-    ProductSurface::new(fork, fork)
+/// Allow the metadata keys alongside the keys for a type of surface
+fn surface_keys(keys: &[&'static str]) -> Vec<&'static str> {
+    keys.iter().chain(METADATA_KEYS.iter()).copied().collect()
 }
 
-pub fn select_model(name: &str) -> Box<dyn Height2D> {
-    match name {
-        "crater_hill" => Box::new(crater_hill()),
-        "crater_diamond" => Box::new(crater_diamond()),
-        "step_hill" => Box::new(step_hill()),
-        "sinc_box" => Box::new(sinc_box()),
-        "sine_hill" => Box::new(SineHill::new()),
-        "peak_rings" => Box::new(peak_rings()),
-        "peak_thing" => Box::new(peak_thing()),
-        "quad_peak" => Box::new(quad_peak()),
-        "nine_peak" => Box::new(nine_peak()),
-        "nine_tines" => Box::new(nine_tines()),
-        _ => panic!("valid models: crater_hill, step_hill, sinc_box")
+fn make_shape(value: &Value) -> Result<Shape, String> {
+    let fields = Fields::new(value, "model")?;
+    match fields.string("type")? {
+        "sphere" | "torus" | "implicit" | "union" | "intersection" |
+                "difference" => {
+            make_solid(value, "model", &METADATA_KEYS).map(Shape::Solid)
+        },
+        _ => make_surface(value).map(Shape::HeightField),
     }
 }
 
-fn lerp(a: f64, b: f64, t: f64) -> f64 {
-    a * (1.0 - t) + b * t
+fn make_surface(value: &Value) -> Result<Box<dyn Height2D>, String> {
+    let fields = Fields::new(value, "model")?;
+    let surface: Box<dyn Height2D> = match fields.string("type")? {
+        "revolution" => {
+            fields.check_keys(&surface_keys(&["type", "metric", "profile"]))?;
+            let metric = match fields.string_or("metric", "euclidean")? {
+                "euclidean" => DistanceMetric::Euclidean,
+                "manhattan" => DistanceMetric::Manhattan,
+                "chessboard" => DistanceMetric::Chessboard,
+                other => return Err(fields.invalid(
+                    "metric",
+                    other,
+                    "euclidean, manhattan, or chessboard")),
+            };
+            let profile = make_profile(fields.required("profile")?, "profile")?;
+            Box::new(SurfaceOfRevolution::new(profile, metric))
+        },
+        "product" => {
+            fields.check_keys(&surface_keys(&["type", "x", "y"]))?;
+            let x_profile = make_profile(fields.required("x")?, "x")?;
+            let y_profile = make_profile(fields.required("y")?, "y")?;
+            Box::new(ProductSurface::new(x_profile, y_profile))
+        },
+        "expression" => {
+            fields.check_keys(&surface_keys(&["type", "formula"]))?;
+            Box::new(fields.formula(Expression::surface)?)
+        },
+        other => return Err(fields.invalid(
+            "type",
+            other,
+            "revolution, product, expression, sphere, torus, implicit, \
+            union, intersection, or difference")),
+    };
+
+    Ok(surface)
+}
+
+/// `extra_keys` are allowed alongside the keys for the type of solid
+fn make_solid(value: &Value, path: &str, extra_keys: &[&'static str])
+        -> Result<Box<dyn Solid>, String> {
+    let fields = Fields::new(value, path)?;
+    let keys = |keys: &[&'static str]| -> Vec<&'static str> {
+        keys.iter().chain(extra_keys.iter()).copied().collect()
+    };
+
+    let solid: Box<dyn Solid> = match fields.string("type")? {
+        "sphere" => {
+            fields.check_keys(&keys(&["type", "center", "radius"]))?;
+            Box::new(Sphere {
+                center: fields.point3_or("center", CUBE_CENTER)?,
+                radius: fields.number("radius")?,
+            })
+        },
+        "torus" => {
+            fields.check_keys(
+                &keys(&["type", "center", "major_radius", "minor_radius"]))?;
+            Box::new(Torus {
+                center: fields.point3_or("center", CUBE_CENTER)?,
+                major_radius: fields.number("major_radius")?,
+                minor_radius: fields.number("minor_radius")?,
+            })
+        },
+        "implicit" => {
+            fields.check_keys(&keys(&["type", "formula"]))?;
+            Box::new(fields.formula(Expression::solid)?)
+        },
+        combination @ ("union" | "intersection" | "difference") => {
+            fields.check_keys(&keys(&["type", "solids"]))?;
+            let operation = match combination {
+                "union" => Operation::Union,
+                "intersection" => Operation::Intersection,
+                _ => Operation::Difference,
+            };
+            let solids = fields.array("solids")?
+                .iter()
+                .enumerate()
+                .map(|(i, solid)| {
+                    make_solid(solid, &format!("{}.solids[{}]", path, i), &[])
+                })
+                .collect::<Result<Vec<Box<dyn Solid>>, String>>()?;
+            if solids.is_empty() {
+                return Err(format!("{}.solids: needs at least one solid", path));
+            }
+            Box::new(Combination { operation, solids })
+        },
+        other => return Err(fields.invalid(
+            "type",
+            other,
+            "sphere, torus, implicit, union, intersection, or difference")),
+    };
+
+    Ok(solid)
 }
 
-fn scaled_t(t: f64, t0: f64, t1: f64) -> f64 {
-    (t - t0) / (t1 - t0)
+fn make_profile(value: &Value, path: &str) -> Result<Box<dyn Height1D>, String> {
+    let fields = Fields::new(value, path)?;
+    let profile: Box<dyn Height1D> = match fields.string("type")? {
+        "polynomial" => {
+            fields.check_keys(&["type", "coefficients"])?;
+            let coefficients = fields.array("coefficients")?
+                .iter()
+                .enumerate()
+                .map(|(i, c)| number(c, &format!("{}.coefficients[{}]", path, i)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            Box::new(Polynomial::new(coefficients))
+        },
+        "piecewise_linear" => {
+            fields.check_keys(&["type", "points"])?;
+            let points = fields.array("points")?
+                .iter()
+                .enumerate()
+                .map(|(i, p)| point(p, &format!("{}.points[{}]", path, i)))
+                .collect::<Result<Vec<(f64, f64)>, String>>()?;
+            if points.is_empty() {
+                return Err(format!("{}.points: needs at least one point", path));
+            }
+            if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                return Err(format!(
+                    "{}.points: x coordinates must be increasing", path));
+            }
+            Box::new(PiecewiseLinear::new(points))
+        },
+        "peaks" => {
+            fields.check_keys(&["type", "combine", "peaks"])?;
+            let combine = match fields.string_or("combine", "sum")? {
+                "sum" => Combine::Sum,
+                "max" => Combine::Max,
+                other => return Err(fields.invalid("combine", other, "sum or max")),
+            };
+            let peaks = fields.array("peaks")?
+                .iter()
+                .enumerate()
+                .map(|(i, p)| make_peak(p, &format!("{}.peaks[{}]", path, i)))
+                .collect::<Result<Vec<Peak>, String>>()?;
+            if peaks.is_empty() {
+                return Err(format!("{}.peaks: needs at least one peak", path));
+            }
+            Box::new(Peaks::new(peaks, combine))
+        },
+        "sine" => {
+            fields.check_keys(&[
+                "type", "amplitude", "frequency", "phase", "offset", "at_zero",
+            ])?;
+            let at_zero = match fields.get("at_zero") {
+                Some(_) => Some(fields.number("at_zero")?),
+                None => None,
+            };
+            Box::new(Sine {
+                amplitude: fields.number("amplitude")?,
+                frequency: fields.number("frequency")?,
+                phase: fields.number_or("phase", 0.0)?,
+                offset: fields.number_or("offset", 0.0)?,
+                at_zero,
+            })
+        },
+        "gaussian" => {
+            fields.check_keys(&["type", "center", "height", "sharpness"])?;
+            Box::new(Gaussian {
+                center: fields.number("center")?,
+                height: fields.number_or("height", 1.0)?,
+                sharpness: fields.number("sharpness")?,
+            })
+        },
+        "expression" => {
+            fields.check_keys(&["type", "formula"])?;
+            Box::new(fields.formula(Expression::profile)?)
+        },
+        other => return Err(fields.invalid(
+            "type",
+            other,
+            "polynomial, piecewise_linear, peaks, sine, gaussian, or expression")),
+    };
+
+    Ok(profile)
 }
 
-fn lerp_heights(a: f64, b: f64, t: f64, t0: f64, t1: f64) -> f64 {
-    lerp(a, b, scaled_t(t, t0, t1))
+fn make_peak(value: &Value, path: &str) -> Result<Peak, String> {
+    let fields = Fields::new(value, path)?;
+    fields.check_keys(&["center", "height", "sharpness"])?;
+
+    Ok(Peak {
+        center: fields.number("center")?,
+        height: fields.number_or("height", 1.0)?,
+        sharpness: fields.number("sharpness")?,
+    })
 }
 
-fn sinc(r: f64) -> f64 {
-    if r == 0.0 {
-        5.0 / 8.0 + 0.35
-    } else {
-        5.0 / 8.0 * (10.0 * r).sin() + 0.35
-    }
+fn number(value: &Value, path: &str) -> Result<f64, String> {
+    value.as_f64().ok_or_else(
+        || format!("{}: expected a number, found {}", path, value.type_name()))
 }
 
-fn steps(r: f64) -> f64 {
-    if r <= 0.2 {
-        1.0
-    } else if r <= 0.3 {
-        lerp_heights(1.0, 0.9, r, 0.2, 0.3)
-    } else if r <= 0.4 {
-        0.9
-    } else if r <= 0.5 {
-        lerp_heights(0.9, 0.7, r, 0.4, 0.5)
-    } else if r <= 0.6 {
-        0.7
-    } else if r <= 0.7 {
-        lerp_heights(0.7, 0.4, r, 0.6, 0.7)
-    } else if r <= 0.9 {
-        0.4
-    } else if r <= 1.0 {
-        lerp_heights(0.4, 0.2, r, 0.9, 1.0)
-    } else {
-        0.2
+fn point(value: &Value, path: &str) -> Result<(f64, f64), String> {
+    match value.as_array() {
+        Some([x, y]) => Ok((number(x, path)?, number(y, path)?)),
+        _ => Err(format!("{}: expected a point like [x, y]", path)),
     }
 }
 
-fn peak(x: f64, n: f64) -> f64 {
-    let exponent = -n * x.abs();
-    exponent.exp()
+/// The fields of a JSON object, with `path` to say where it is in the file
+struct Fields<'a> {
+    path: &'a str,
+    fields: &'a [(String, Value)],
 }
 
-fn double_peak(x: f64) -> f64 {
-    const A: f64 = 0.25;
-    const B: f64 = 0.75;
-    const C: f64 = 0.9;
-    const D: f64 = 0.7;
-    const N: f64 = 3.8;
+impl<'a> Fields<'a> {
+    fn new(value: &'a Value, path: &'a str) -> Result<Self, String> {
+        let fields = value.as_object().ok_or_else(|| format!(
+            "{}: expected an object, found {}", path, value.type_name()))?;
 
-    C * peak(x - A, N) + D * peak(x - B, N)
-}
+        Ok(Self { path, fields })
+    }
+
+    /// Catch typos by rejecting keys that aren't in `allowed`
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.fields.iter().find(|(key, _)| !allowed.contains(&key.as_str())) {
+            Some((key, _)) => Err(format!(
+                "{}: unknown key '{}', expected one of: {}",
+                self.path,
+                key,
+                allowed.join(", "))),
+            None => Ok(()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    fn required(&self, key: &str) -> Result<&'a Value, String> {
+        self.get(key)
+            .ok_or_else(|| format!("{}: missing key '{}'", self.path, key))
+    }
 
-fn triple_peak(x: f64) -> f64 {
-    const A: f64 = 0.25;
-    const B: f64 = 0.5; 
-    const C: f64 = 0.75;
+    fn key_path(&self, key: &str) -> String {
+        format!("{}.{}", self.path, key)
+    }
+
+    fn invalid(&self, key: &str, value: &str, expected: &str) -> String {
+        format!(
+            "{}: unknown value '{}', expected {}",
+            self.key_path(key),
+            value,
+            expected)
+    }
+
+    fn number(&self, key: &str) -> Result<f64, String> {
+        number(self.required(key)?, &self.key_path(key))
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, String> {
+        self.get(key).map_or(Ok(default), |_| self.number(key))
+    }
+
+    /// A whole number that is at least 0
+    fn count(&self, key: &str) -> Result<u32, String> {
+        let x = self.number(key)?;
+        if x >= 0.0 && x.fract() == 0.0 && x <= u32::MAX as f64 {
+            Ok(x as u32)
+        } else {
+            Err(format!("{}: expected a whole number, found {}", self.key_path(key), x))
+        }
+    }
+
+    fn point3_or(&self, key: &str, default: Vec3) -> Result<Vec3, String> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(default),
+        };
+
+        let path = self.key_path(key);
+        match value.as_array() {
+            Some([x, y, z]) => Ok(Vec3(
+                number(x, &path)?,
+                number(y, &path)?,
+                number(z, &path)?)),
+            _ => Err(format!("{}: expected a point like [x, y, z]", path)),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<&'a str, String> {
+        let value = self.required(key)?;
+        value.as_str().ok_or_else(|| format!(
+            "{}: expected a string, found {}",
+            self.key_path(key),
+            value.type_name()))
+    }
+
+    fn string_or(&self, key: &str, default: &'a str) -> Result<&'a str, String> {
+        self.get(key).map_or(Ok(default), |_| self.string(key))
+    }
 
-    const D: f64 = 0.4;
-    const E: f64 = 0.9;
-    const N: f64 = 10.0;
-    1.2 * (D * peak(x - A, N) + E * peak(x - B, N) + D * peak(x - C, N))
+    fn formula(&self, parse: fn(&str) -> Result<Expression, expression::ParseError>)
+            -> Result<Expression, String> {
+        let text = self.string("formula")?;
+        parse(text).map_err(
+            |err| format!("{}: {}", self.key_path("formula"), err))
+    }
+
+    fn array(&self, key: &str) -> Result<&'a [Value], String> {
+        let value = self.required(key)?;
+        value.as_array().ok_or_else(|| format!(
+            "{}: expected an array, found {}",
+            self.key_path(key),
+            value.type_name()))
+    }
 }
 
-fn fork(x: f64) -> f64 {
-    const N1: f64 = 3.0;
-    const N2: f64 = 8.0;
-    const X1: f64 = 0.1;
-    const X2: f64 = 0.5;
-    const X3: f64 = 0.9;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn height_field(text: &str) -> Box<dyn Height2D> {
+        match parse_model("test", text).unwrap().shape {
+            Shape::HeightField(surface) => surface,
+            Shape::Solid(_) => panic!("expected a height field"),
+        }
+    }
+
+    fn invalid(text: &str) -> String {
+        parse_model("m.json", text).err().expect("expected an error").to_string()
+    }
+
+    #[test]
+    fn metadata() {
+        let info = parse_model("m.json", r#"{
+            "type": "expression",
+            "formula": "x",
+            "description": "A ramp",
+            "slice_res": 3,
+            "width": 4,
+            "domain": "circle"
+        }"#).unwrap().info;
+        assert_eq!(info.description, "A ramp");
+        assert_eq!(info.slice_res, 3);
+        assert_eq!(info.curve_res, DEFAULT_CURVE_RES);
+        assert_eq!(info.width, 4.0);
+        assert!(info.domain.is_circle());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            invalid(r#"{ "type": "expression" }"#),
+            "m.json: model: missing key 'formula'");
+        assert_eq!(
+            invalid(r#"{ "type": "expression", "formula": "x", "width": -1 }"#),
+            "m.json: model.width: must be positive");
+        assert_eq!(
+            invalid(r#"{ "type": "product", "x": { "type": "sine" }, "y": 1 }"#),
+            "m.json: x: missing key 'amplitude'");
+        assert!(invalid(r#"{ "type": "expression", "formula": "x", "z": 1 }"#)
+            .contains("unknown key 'z'"));
+        assert!(invalid(r#"{ "type": "revolution", "profile": { "type": "sine",
+            "amplitude": 1, "frequency": 1, "at_zero": "top" } }"#)
+            .starts_with("m.json: profile.at_zero"));
+        assert!(invalid(r#"{ "type": "sphere", "center": [0.5, 0.5, 0.5],
+            "radius": 0.4, "domain": "circle" }"#)
+            .contains("only height fields have a domain"));
+        assert!(invalid("{ \"type\": ").starts_with("m.json: line 1"));
+    }
+
+    #[test]
+    fn bundled_models_parse() {
+        for info in Registry::bundled().models() {
+            assert!(!info.description.is_empty(), "{}", info.name);
+        }
+    }
+
+    #[test]
+    fn only_euclidean_revolutions_are_round() {
+        let round = |text: &str| parse_model("test", text).unwrap().info.round;
+        let profile = r#""profile": { "type": "polynomial", "coefficients": [1] }"#;
+        assert!(round(&format!(r#"{{ "type": "revolution", {} }}"#, profile)));
+        assert!(!round(&format!(
+            r#"{{ "type": "revolution", "metric": "manhattan", {} }}"#,
+            profile)));
+        assert!(!round(r#"{ "type": "expression", "formula": "r" }"#));
+    }
 
-    let f1 = peak(x - X1, N1);
-    let f2 = peak(x - X2, N2);
-    let f3 = peak(x - X3, N1);
-    
-    f1.max(f2).max(f3)
+    #[test]
+    fn sinc_box_peaks_at_center() {
+        let surface = height_field(include_str!("../models/sinc_box.json"));
+        assert_eq!(surface.compute(0.5, 0.5), 5.0 / 8.0 + 0.35);
+        for &x in [0.55_f64, 0.7, 0.8, 1.0].iter() {
+            let r = 2.0 * x - 1.0;
+            let expected = 5.0 / 8.0 * (10.0 * r).sin() + 0.35;
+            for height in [surface.compute(x, 0.5), surface.compute(0.5, x)] {
+                assert!((height - expected).abs() < 1e-12, "x = {}", x);
+            }
+        }
+    }
 }
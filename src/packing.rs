@@ -0,0 +1,375 @@
+use std::cmp::Ordering;
+
+// Tolerance for rectangles that fit exactly
+const EPSILON: f64 = 1e-9;
+
+// Orderings to try, each a key of (width, height) to sort by, largest first
+const ORDERINGS: [fn(f64, f64) -> f64; 4] = [
+    |w, h| w.max(h),
+    |w, h| w * h,
+    |_, h| h,
+    |w, _| w,
+];
+
+#[derive(Debug, Copy, Clone)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn top(&self) -> f64 {
+        self.y + self.height
+    }
+
+    fn fits(&self, width: f64, height: f64) -> bool {
+        width <= self.width + EPSILON && height <= self.height + EPSILON
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right() - EPSILON &&
+            other.x < self.right() - EPSILON &&
+            self.y < other.top() - EPSILON &&
+            other.y < self.top() - EPSILON
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x - EPSILON &&
+            other.y >= self.y - EPSILON &&
+            other.right() <= self.right() + EPSILON &&
+            other.top() <= self.top() + EPSILON
+    }
+}
+
+/// Where one item ended up. `x` and `y` are the bottom left corner of its
+/// bounding box relative to the bottom left corner of the page.
+#[derive(Debug, Copy, Clone)]
+pub struct Packed {
+    pub page: usize,
+    pub x: f64,
+    pub y: f64,
+    /// Turned 90 degrees counterclockwise, so width and height swap
+    pub rotated: bool,
+    /// Too big for the page even when rotated. These get a page of their
+    /// own and hang off the edge.
+    pub oversized: bool,
+}
+
+/// One page worth of free space, tracked as the maximal empty rectangles
+/// (the MaxRects algorithm). The rectangles may overlap each other.
+struct Bin {
+    free: Vec<Rect>,
+}
+
+impl Bin {
+    fn new(width: f64, height: f64) -> Self {
+        Self {
+            free: vec![Rect { x: 0.0, y: 0.0, width, height }],
+        }
+    }
+
+    /// Best short side fit: pick the free rectangle that leaves the least
+    /// room along its tighter side. Returns the position and the leftover
+    /// (short side, long side), smaller is better.
+    fn find_position(&self, width: f64, height: f64)
+            -> Option<(Rect, (f64, f64))> {
+        let mut best: Option<(Rect, (f64, f64))> = None;
+        for free in self.free.iter().filter(|free| free.fits(width, height)) {
+            let dx = free.width - width;
+            let dy = free.height - height;
+            let score = (dx.min(dy), dx.max(dy));
+            if best.is_none_or(|(_, best_score)| score < best_score) {
+                let rect = Rect { x: free.x, y: free.y, width, height };
+                best = Some((rect, score));
+            }
+        }
+
+        best
+    }
+
+    fn place(&mut self, used: &Rect) {
+        let mut split = Vec::new();
+        for free in self.free.iter() {
+            if !free.intersects(used) {
+                split.push(*free);
+                continue;
+            }
+
+            // Keep whatever is left on each side of the used rectangle
+            if used.x > free.x {
+                split.push(Rect { width: used.x - free.x, ..*free });
+            }
+            if used.right() < free.right() {
+                split.push(Rect {
+                    x: used.right(),
+                    width: free.right() - used.right(),
+                    ..*free
+                });
+            }
+            if used.y > free.y {
+                split.push(Rect { height: used.y - free.y, ..*free });
+            }
+            if used.top() < free.top() {
+                split.push(Rect {
+                    y: used.top(),
+                    height: free.top() - used.top(),
+                    ..*free
+                });
+            }
+        }
+
+        // Drop rectangles that another one already covers. Of two equal
+        // rectangles, keep the first.
+        self.free = split.iter()
+            .enumerate()
+            .filter(|(i, rect)| {
+                !split.iter().enumerate().any(|(j, other)| {
+                    *i != j && other.contains(rect) &&
+                        (!rect.contains(other) || j < *i)
+                })
+            })
+            .map(|(_, rect)| *rect)
+            .collect();
+    }
+}
+
+/// Packs rectangles onto as few pages as it can.
+pub struct Packer {
+    width: f64,
+    height: f64,
+    spacing: f64,
+    allow_rotation: bool,
+}
+
+impl Packer {
+    /// Pack onto pages with `width` x `height` of usable space
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            spacing: 0.0,
+            allow_rotation: false,
+        }
+    }
+
+    /// Leave at least `spacing` between neighboring items
+    pub fn with_spacing(mut self, spacing: f64) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Let items turn 90 degrees when that packs them better. Half turns
+    /// never change a bounding box, so they are not considered.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Pack items given as (width, height). The result is in the same order
+    /// as the input.
+    ///
+    /// Items are placed largest first, each in the best spot on any page
+    /// opened so far. A few orderings are tried and the one with the fewest
+    /// pages wins.
+    pub fn pack(&self, sizes: &[(f64, f64)]) -> Vec<Packed> {
+        let mut best: Option<(usize, Vec<Packed>)> = None;
+        for key in ORDERINGS.iter() {
+            let size_key = |i: usize| key(sizes[i].0, sizes[i].1);
+            let mut order: Vec<usize> = (0..sizes.len()).collect();
+            order.sort_by(|&a, &b| {
+                size_key(b)
+                    .partial_cmp(&size_key(a))
+                    .unwrap_or(Ordering::Equal)
+            });
+
+            let packed = self.pack_in_order(sizes, &order);
+            let page_count = count_pages(&packed);
+            if best.as_ref().is_none_or(|(count, _)| page_count < *count) {
+                best = Some((page_count, packed));
+            }
+        }
+
+        best.map_or(Vec::new(), |(_, packed)| packed)
+    }
+
+    fn pack_in_order(&self, sizes: &[(f64, f64)], order: &[usize])
+            -> Vec<Packed> {
+        // Every item gets the spacing on its top and right. Growing the page
+        // by the same amount means items can still touch the far edges.
+        let bin_width = self.width + self.spacing;
+        let bin_height = self.height + self.spacing;
+
+        let mut bins: Vec<Bin> = Vec::new();
+        let mut packed = vec![None; sizes.len()];
+        for &index in order.iter() {
+            let (width, height) = sizes[index];
+            let width = width + self.spacing;
+            let height = height + self.spacing;
+
+            let mut orientations = vec![(width, height, false)];
+            if self.allow_rotation && width != height {
+                orientations.push((height, width, true));
+            }
+
+            // Best spot over every open page and orientation
+            let mut best: Option<(usize, Rect, bool, (f64, f64))> = None;
+            for (page, bin) in bins.iter().enumerate() {
+                for &(w, h, rotated) in orientations.iter() {
+                    if let Some((rect, score)) = bin.find_position(w, h) {
+                        let better = best.is_none_or(
+                            |(_, _, _, best_score)| score < best_score);
+                        if better {
+                            best = Some((page, rect, rotated, score));
+                        }
+                    }
+                }
+            }
+
+            // Otherwise start a new page
+            if best.is_none() {
+                let bin = Bin::new(bin_width, bin_height);
+                best = orientations.iter()
+                    .filter_map(|&(w, h, rotated)| {
+                        bin.find_position(w, h)
+                            .map(|(rect, score)| (bins.len(), rect, rotated, score))
+                    })
+                    .min_by(|a, b| {
+                        a.3.partial_cmp(&b.3).unwrap_or(Ordering::Equal)
+                    });
+                bins.push(bin);
+            }
+
+            packed[index] = Some(match best {
+                Some((page, rect, rotated, _)) => {
+                    bins[page].place(&rect);
+                    Packed {
+                        page,
+                        x: rect.x,
+                        y: rect.y,
+                        rotated,
+                        oversized: false,
+                    }
+                },
+                None => {
+                    // Nothing can share the page with an oversized item
+                    let page = bins.len() - 1;
+                    bins[page].free.clear();
+                    Packed {
+                        page,
+                        x: 0.0,
+                        y: 0.0,
+                        rotated: false,
+                        oversized: true,
+                    }
+                },
+            });
+        }
+
+        packed.into_iter()
+            .map(|p| p.expect("every item was packed"))
+            .collect()
+    }
+}
+
+fn count_pages(packed: &[Packed]) -> usize {
+    packed.iter().map(|p| p.page + 1).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The rectangle each item takes up on its page
+    fn footprints(sizes: &[(f64, f64)], packed: &[Packed]) -> Vec<(usize, Rect)> {
+        sizes.iter()
+            .zip(packed)
+            .map(|(&(w, h), p)| {
+                let (width, height) = if p.rotated { (h, w) } else { (w, h) };
+                (p.page, Rect { x: p.x, y: p.y, width, height })
+            })
+            .collect()
+    }
+
+    fn assert_no_overlaps(placed: &[(usize, Rect)], width: f64, height: f64) {
+        let page = Rect { x: 0.0, y: 0.0, width, height };
+        for (i, (page_a, a)) in placed.iter().enumerate() {
+            assert!(page.contains(a), "{:?} is off the page", a);
+            for (page_b, b) in placed[i + 1..].iter() {
+                assert!(page_a != page_b || !a.intersects(b),
+                    "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_a_page_exactly() {
+        let sizes = vec![(5.0, 5.0); 4];
+        let packed = Packer::new(10.0, 10.0).pack(&sizes);
+        assert_eq!(count_pages(&packed), 1);
+        assert_no_overlaps(&footprints(&sizes, &packed), 10.0, 10.0);
+    }
+
+    #[test]
+    fn many_sizes() {
+        // A fixed pseudo-random sequence
+        let mut seed: u32 = 1;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            1.0 + f64::from(seed >> 16) / 65536.0 * 20.0
+        };
+        let sizes: Vec<(f64, f64)> = (0..60).map(|_| (next(), next())).collect();
+        let area: f64 = sizes.iter().map(|(w, h)| w * h).sum();
+
+        for &rotate in [false, true].iter() {
+            let packed = Packer::new(50.0, 40.0).with_rotation(rotate).pack(&sizes);
+            assert!(packed.iter().all(|p| !p.oversized));
+            assert!(rotate || packed.iter().all(|p| !p.rotated));
+            assert_no_overlaps(&footprints(&sizes, &packed), 50.0, 40.0);
+            // Far better than one item per page
+            assert!(count_pages(&packed) <= (area / 2000.0 * 1.5).ceil() as usize);
+        }
+    }
+
+    #[test]
+    fn spacing_keeps_items_apart() {
+        let sizes = [(5.0, 10.0), (5.0, 10.0)];
+        assert_eq!(count_pages(&Packer::new(10.0, 10.0).pack(&sizes)), 1);
+
+        let packed = Packer::new(10.0, 10.0).with_spacing(1.0).pack(&sizes);
+        assert_eq!(count_pages(&packed), 2);
+
+        let packed = Packer::new(11.0, 10.0).with_spacing(1.0).pack(&sizes);
+        assert_eq!(count_pages(&packed), 1);
+        assert_eq!((packed[0].x - packed[1].x).abs(), 6.0);
+    }
+
+    #[test]
+    fn rotation() {
+        let sizes = [(10.0, 2.0)];
+        assert!(Packer::new(4.0, 12.0).pack(&sizes)[0].oversized);
+
+        let packed = Packer::new(4.0, 12.0).with_rotation(true).pack(&sizes);
+        assert!(packed[0].rotated && !packed[0].oversized);
+    }
+
+    #[test]
+    fn oversized_items_get_their_own_page() {
+        let sizes = [(1.0, 1.0), (20.0, 1.0), (1.0, 1.0)];
+        let packed = Packer::new(10.0, 10.0).pack(&sizes);
+        assert!(packed[1].oversized);
+        assert_eq!(count_pages(&packed), 2);
+        assert_eq!(packed[0].page, packed[2].page);
+        assert_ne!(packed[0].page, packed[1].page);
+    }
+
+    #[test]
+    fn nothing_to_pack() {
+        assert!(Packer::new(10.0, 10.0).pack(&[]).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use crate::units::{self, INCH};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "portrait" => Ok(Orientation::Portrait),
+            "landscape" => Ok(Orientation::Landscape),
+            _ => Err(String::from("expected portrait or landscape")),
+        }
+    }
+}
+
+/// Page dimensions in points, before applying an orientation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PaperSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PaperSize {
+    const MM: f64 = INCH / 25.4;
+
+    pub const LETTER: Self = Self::new(8.5 * INCH, 11.0 * INCH);
+    pub const LEGAL: Self = Self::new(8.5 * INCH, 14.0 * INCH);
+    pub const TABLOID: Self = Self::new(11.0 * INCH, 17.0 * INCH);
+    pub const A4: Self = Self::new(210.0 * Self::MM, 297.0 * Self::MM);
+    pub const A3: Self = Self::new(297.0 * Self::MM, 420.0 * Self::MM);
+
+    pub const PRESETS: [(&'static str, Self); 5] = [
+        ("letter", Self::LETTER),
+        ("legal", Self::LEGAL),
+        ("tabloid", Self::TABLOID),
+        ("a4", Self::A4),
+        ("a3", Self::A3),
+    ];
+
+    pub const fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height
+        }
+    }
+
+    /// Turn the page so the long side is vertical (portrait) or horizontal
+    /// (landscape).
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let short = self.width.min(self.height);
+        let long = self.width.max(self.height);
+        match orientation {
+            Orientation::Portrait => Self::new(short, long),
+            Orientation::Landscape => Self::new(long, short),
+        }
+    }
+}
+
+impl FromStr for PaperSize {
+    type Err = String;
+
+    /// Either the name of a preset like "a4", or a custom size like
+    /// "600x400mm" or "8.5x11in" (width first).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        if let Some((_, size)) = Self::PRESETS
+                .iter()
+                .find(|(preset, _)| *preset == name) {
+            return Ok(*size);
+        }
+
+        let (width, height) = match name.split_once('x') {
+            Some(parts) => parts,
+            None => {
+                let presets: Vec<&str> = Self::PRESETS
+                    .iter()
+                    .map(|(preset, _)| *preset)
+                    .collect();
+                return Err(format!(
+                    "expected {} or a size like 600x400mm",
+                    presets.join(", ")));
+            }
+        };
+
+        // "8.5x11in" puts the unit on the height only, so share it
+        let unit = height.trim_start_matches(|c: char| {
+            c.is_ascii_digit() || c == '.'
+        });
+        let width = if width.ends_with(unit) {
+            width.to_string()
+        } else {
+            format!("{}{}", width, unit)
+        };
+
+        let width = units::parse_length(&width)?;
+        let height = units::parse_length(height)?;
+        if width <= 0.0 || height <= 0.0 {
+            return Err(String::from("page size must be positive"));
+        }
+
+        Ok(Self::new(width, height))
+    }
+}
+
+/// Space to leave blank on each side of the page, in points.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Margins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Margins {
+    pub fn uniform(margin: f64) -> Self {
+        Self {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+        }
+    }
+}
+
+/// The paper to print on and the area of it available for slices.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PageFormat {
+    pub width: f64,
+    pub height: f64,
+    pub margins: Margins,
+}
+
+impl PageFormat {
+    /// `orientation` of None keeps the size as given, which is handy for
+    /// custom sizes like a laser bed.
+    pub fn new(
+            size: PaperSize,
+            orientation: Option<Orientation>,
+            margins: Margins)
+            -> Self {
+        let size = match orientation {
+            Some(orientation) => size.oriented(orientation),
+            None => size,
+        };
+
+        Self {
+            width: size.width,
+            height: size.height,
+            margins,
+        }
+    }
+
+    pub fn printable_width(&self) -> f64 {
+        self.width - self.margins.left - self.margins.right
+    }
+
+    pub fn printable_height(&self) -> f64 {
+        self.height - self.margins.top - self.margins.bottom
+    }
+}
+
+impl Default for PageFormat {
+    /// Letter-sized paper with 1/2 inch margins
+    fn default() -> Self {
+        Self::new(
+            PaperSize::LETTER,
+            Some(Orientation::Portrait),
+            Margins::uniform(0.5 * INCH))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(s: &str) -> (f64, f64) {
+        let paper: PaperSize = s.parse().unwrap();
+        (paper.width, paper.height)
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(size("letter"), (612.0, 792.0));
+        assert_eq!(size("A4"), (PaperSize::A4.width, PaperSize::A4.height));
+    }
+
+    #[test]
+    fn custom_sizes_share_the_unit() {
+        assert_eq!(size("8.5x11in"), (612.0, 792.0));
+        assert_eq!(size("8.5inx11in"), (612.0, 792.0));
+        assert_eq!(size("25.4x50.8mm"), (72.0, 144.0));
+        assert_eq!(size("1x2"), (72.0, 144.0));
+    }
+
+    #[test]
+    fn errors() {
+        for s in ["huge", "0x10in", "10x", "axb", "10x10cm"].iter() {
+            assert!(s.parse::<PaperSize>().is_err(), "{}", s);
+        }
+        assert!("sideways".parse::<Orientation>().is_err());
+    }
+
+    #[test]
+    fn orientation() {
+        let landscape = PaperSize::LETTER.oriented(Orientation::Landscape);
+        assert_eq!(landscape, PaperSize::new(792.0, 612.0));
+        assert_eq!(
+            landscape.oriented(Orientation::Portrait),
+            PaperSize::LETTER);
+    }
+
+    #[test]
+    fn printable_area() {
+        let margins = Margins { top: 1.0, right: 2.0, bottom: 3.0, left: 4.0 };
+        let page = PageFormat::new(PaperSize::new(100.0, 50.0), None, margins);
+        assert_eq!(page.printable_width(), 94.0);
+        assert_eq!(page.printable_height(), 46.0);
+
+        let page = PageFormat::default();
+        assert_eq!((page.printable_width(), page.printable_height()),
+            (540.0, 720.0));
+    }
+}
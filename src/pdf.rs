@@ -0,0 +1,280 @@
+use std::io::{self, Write};
+
+use crate::backend::{Backend, Style};
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+use crate::postscript::escape_string;
+
+/// Writes a multi-page PDF directly so templates can be made without
+/// Ghostscript. Page contents are buffered and the whole document is
+/// written by `finish()`.
+pub struct PdfBackend<W: Write> {
+    out: W,
+    // Finished pages: the MediaBox size and the content stream
+    pages: Vec<(f64, f64, Vec<u8>)>,
+    current: Vec<u8>,
+    page_size: (f64, f64),
+    // Byte offset of every object written so far, for the xref table
+    offsets: Vec<usize>,
+    position: usize,
+}
+
+impl<W: Write> PdfBackend<W> {
+    // Objects 1 and 2 are always the catalog and the page tree
+    const CATALOG_ID: usize = 1;
+    const PAGES_ID: usize = 2;
+
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            pages: Vec::new(),
+            current: Vec::new(),
+            page_size: (0.0, 0.0),
+            offsets: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.position += bytes.len();
+
+        Ok(())
+    }
+
+    /// Write object number `offsets.len() + 1` with the given body
+    fn write_object(&mut self, body: &[u8]) -> io::Result<()> {
+        self.offsets.push(self.position);
+        let id = self.offsets.len();
+
+        self.write_bytes(format!("{} 0 obj\n", id).as_bytes())?;
+        self.write_bytes(body)?;
+        self.write_bytes(b"\nendobj\n")
+    }
+
+    fn write_xref(&mut self) -> io::Result<()> {
+        let xref_position = self.position;
+        let mut xref = format!(
+            "xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in self.offsets.iter() {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R >>\n",
+            self.offsets.len() + 1,
+            Self::CATALOG_ID));
+        xref.push_str(&format!("startxref\n{}\n%%EOF\n", xref_position));
+
+        self.write_bytes(xref.as_bytes())
+    }
+}
+
+impl<W: Write> Backend for PdfBackend<W> {
+    fn begin_page(&mut self, width: f64, height: f64) -> io::Result<()> {
+        self.page_size = (width, height);
+        self.current.clear();
+
+        Ok(())
+    }
+
+    fn draw_polyline(&mut self, polyline: &Polyline, style: Style)
+            -> io::Result<()> {
+        let vertices = polyline.vertices();
+
+        // Don't write individual points.
+        if vertices.len() < 2 {
+            return Ok(());
+        }
+
+        // Marks are stroked in their own color inside q ... Q, so the
+        // color doesn't carry over to the cuts
+        if !style.is_cut() {
+            let (r, g, b) = style.color();
+            writeln!(self.current, "q {} {} {} RG", r, g, b)?;
+        }
+
+        let Vec2(x, y) = vertices[0];
+        writeln!(self.current, "{} {} m", x, y)?;
+
+        for Vec2(x, y) in vertices[1..].iter() {
+            writeln!(self.current, "{} {} l", x, y)?;
+        }
+        writeln!(self.current, "S")?;
+
+        if !style.is_cut() {
+            writeln!(self.current, "Q")?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_text(&mut self, text: &str, position: Vec2, size: f64, angle: f64)
+            -> io::Result<()> {
+        // The text matrix rotates and moves the text in one go
+        let Vec2(x, y) = position;
+        // Rounded so quarter turns give exact zeros
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (sin, cos) = ((sin * 1e9).round() / 1e9, (cos * 1e9).round() / 1e9);
+        // Labels are marks, not cuts, so they get the label color
+        let (r, g, b) = Style::Label.color();
+        writeln!(
+            self.current,
+            "q {} {} {} rg BT /F1 {} Tf {} {} {} {} {} {} Tm ({}) Tj ET Q",
+            r,
+            g,
+            b,
+            size,
+            cos,
+            sin,
+            0.0 - sin,
+            cos,
+            x,
+            y,
+            escape_string(text))
+    }
+
+    fn end_page(&mut self) -> io::Result<()> {
+        let (width, height) = self.page_size;
+        let stream = std::mem::take(&mut self.current);
+        self.pages.push((width, height, stream));
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let pages = std::mem::take(&mut self.pages);
+
+        // Each page is a page object followed by its content stream
+        let page_ids: Vec<usize> = (0..pages.len())
+            .map(|i| Self::PAGES_ID + 1 + 2 * i)
+            .collect();
+
+        // The binary comment marks the file as binary for transfer tools
+        self.write_bytes(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n")?;
+
+        let catalog = format!(
+            "<< /Type /Catalog /Pages {} 0 R >>", Self::PAGES_ID);
+        self.write_object(catalog.as_bytes())?;
+
+        let kids: Vec<String> = page_ids
+            .iter()
+            .map(|id| format!("{} 0 R", id))
+            .collect();
+        let page_tree = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len());
+        self.write_object(page_tree.as_bytes())?;
+
+        for ((width, height, stream), id) in pages.iter().zip(page_ids.iter()) {
+            let page_object = format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+                /Resources << /Font << /F1 << /Type /Font /Subtype /Type1 \
+                /BaseFont /Helvetica >> >> >> /Contents {} 0 R >>",
+                Self::PAGES_ID,
+                width,
+                height,
+                id + 1);
+            self.write_object(page_object.as_bytes())?;
+
+            let mut contents = format!(
+                "<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+            contents.extend_from_slice(stream);
+            contents.extend_from_slice(b"\nendstream");
+            self.write_object(&contents)?;
+        }
+
+        self.write_xref()?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::draw_piece;
+
+    fn render() -> Vec<u8> {
+        let mut out = Vec::new();
+        draw_piece(&mut PdfBackend::new(&mut out)).unwrap();
+        out
+    }
+
+    /// The text after `start`, which is plain ASCII past the header
+    fn text_from(pdf: &[u8], start: usize) -> String {
+        String::from_utf8(pdf[start..].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn xref_points_at_every_object() {
+        let pdf = render();
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        // The header has binary bytes, so look for startxref from the end
+        let tail = text_from(&pdf, pdf.len() - 32);
+        let start: usize = tail
+            .split("startxref\n")
+            .nth(1)
+            .and_then(|rest| rest.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let xref = text_from(&pdf, start);
+        let mut lines = xref.lines();
+        assert_eq!(lines.next(), Some("xref"));
+        // Catalog, page tree, then a page and its contents
+        assert_eq!(lines.next(), Some("0 5"));
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        for id in 1..5 {
+            let entry = lines.next().unwrap();
+            assert_eq!(entry.len(), 19);
+            assert!(entry.ends_with(" 00000 n "));
+            let offset: usize = entry[..10].parse().unwrap();
+            let object = format!("{} 0 obj\n", id);
+            assert!(text_from(&pdf, offset).starts_with(&object));
+        }
+        assert_eq!(lines.next(), Some("trailer"));
+        assert_eq!(lines.next(), Some("<< /Size 5 /Root 1 0 R >>"));
+    }
+
+    #[test]
+    fn pages_and_contents() {
+        let pdf = render();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("<< /Type /Pages /Kids [3 0 R] /Count 1 >>"));
+        assert!(text.contains("/MediaBox [0 0 612 792]"));
+        assert!(text.contains("/Contents 4 0 R"));
+
+        // The stream is as long as it says
+        let (_, stream) = text.split_once("4 0 obj\n<< /Length ").unwrap();
+        let (length, stream) = stream.split_once(" >>\nstream\n").unwrap();
+        let (contents, _) = stream.split_once("\nendstream").unwrap();
+        assert_eq!(contents.len(), length.parse::<usize>().unwrap());
+        assert!(contents.starts_with("72 72 m\n144 72 l\n"));
+        assert_eq!(contents.matches("\nS\n").count(), 3);
+
+        // Cuts are black, and the label and its arrow are marked in blue
+        assert!(contents.contains("72 72 l\nS\n108 144 m\n"));
+        assert!(contents.contains("q 0 0 1 RG\n92 76 m\n"));
+        assert!(contents.ends_with("92 76 l\nS\nQ\n"));
+        assert!(contents.contains(
+            "q 0 0 1 rg BT /F1 10 Tf 1 0 0 1 76 76 Tm (X1) Tj ET Q"));
+    }
+
+    #[test]
+    fn pages_keep_their_sizes() {
+        let mut out = Vec::new();
+        let mut backend = PdfBackend::new(&mut out);
+        for &(width, height) in [(612.0, 792.0), (842.0, 595.0)].iter() {
+            backend.begin_page(width, height).unwrap();
+            backend.end_page().unwrap();
+        }
+        backend.finish().unwrap();
+
+        let text = String::from_utf8_lossy(&out);
+        assert!(text.contains("/Kids [3 0 R 5 0 R] /Count 2"));
+        assert!(text.contains("/MediaBox [0 0 612 792]"));
+        assert!(text.contains("/MediaBox [0 0 842 595]"));
+    }
+}
@@ -1,8 +1,31 @@
-use std::fs::File;
-use std::io::Write;
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
 
 use crate::geom::Vec2;
 
+/// How to fill the gap at a corner when offsetting a polygon outward.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Join {
+    /// Extend the edges until they meet, falling back to a round join for
+    /// very sharp corners where the point would stick out too far.
+    Miter,
+    /// Go around the corner with an arc.
+    Round,
+}
+
+impl FromStr for Join {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "miter" => Ok(Join::Miter),
+            "round" => Ok(Join::Round),
+            _ => Err(String::from("expected miter or round")),
+        }
+    }
+}
+
 pub struct BBox {
     left: f64,
     right: f64,
@@ -13,10 +36,10 @@ pub struct BBox {
 impl BBox {
     pub fn new() -> Self {
         Self {
-            left: std::f64::INFINITY,
-            right: std::f64::NEG_INFINITY,
-            top: std::f64::NEG_INFINITY,
-            bottom: std::f64::INFINITY,
+            left: f64::INFINITY,
+            right: f64::NEG_INFINITY,
+            top: f64::NEG_INFINITY,
+            bottom: f64::INFINITY,
         }
     }
 
@@ -41,11 +64,26 @@ impl BBox {
     }
 
     pub fn width(&self) -> f64 {
-        return self.right - self.left;
+        self.right - self.left
     }
 
     pub fn height(&self) -> f64 {
-        return self.top - self.bottom;
+        self.top - self.bottom
+    }
+
+    pub fn bottom_left(&self) -> Vec2 {
+        Vec2(self.left, self.bottom)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2(
+            (self.left + self.right) / 2.0,
+            (self.bottom + self.top) / 2.0)
+    }
+
+    pub fn overlaps(&self, other: &BBox) -> bool {
+        self.left < other.right && other.left < self.right &&
+            self.bottom < other.top && other.bottom < self.top
     }
 
     pub fn get_outline(&self) -> Polyline {
@@ -85,6 +123,18 @@ impl Polyline {
         }
     }
 
+    /// Scale by `x` across and `y` up
+    pub fn stretch(&self, x: f64, y: f64) -> Self {
+        let vertices = self.vertices
+            .iter()
+            .map(|&Vec2(u, v)| Vec2(u * x, v * y))
+            .collect();
+
+        Self {
+            vertices
+        }
+    }
+
     pub fn translate(&self, amount: Vec2) -> Self {
         let vertices = self.vertices
             .iter()
@@ -96,6 +146,17 @@ impl Polyline {
         }
     }
 
+    pub fn rotate_quarter(&self) -> Self {
+        let vertices = self.vertices
+            .iter()
+            .map(|v| v.rotate_quarter())
+            .collect();
+
+        Self {
+            vertices
+        }
+    }
+
     pub fn get_bbox(&self) -> BBox {
         let mut bbox = BBox::new();
         for v in self.vertices.iter() {
@@ -105,73 +166,439 @@ impl Polyline {
         bbox
     }
 
-    pub fn write_postscript(&self, file: &mut File) {
-        // Don't write individual points.
-        if self.vertices.len() < 2 {
-            return;
+    pub fn vertices(&self) -> &[Vec2] {
+        &self.vertices
+    }
+
+    /// Even-odd test for whether a point is inside a closed polyline
+    pub fn contains(&self, point: Vec2) -> bool {
+        let Vec2(px, py) = point;
+        let mut inside = false;
+        for pair in self.vertices.windows(2) {
+            let Vec2(x1, y1) = pair[0];
+            let Vec2(x2, y2) = pair[1];
+            if (y1 > py) != (y2 > py) {
+                let x = x1 + (py - y1) / (y2 - y1) * (x2 - x1);
+                if x > px {
+                    inside = !inside;
+                }
+            }
         }
 
-        writeln!(file, "newpath").expect("Failed to write newpath");
+        inside
+    }
 
-        let Vec2(x, y) = self.vertices[0]; 
-        writeln!(file, "{} {} moveto", x, y).expect("failed to write moveto");
+    pub fn is_closed(&self) -> bool {
+        match (self.vertices.first(), self.vertices.last()) {
+            (Some(Vec2(x1, y1)), Some(Vec2(x2, y2))) =>
+                self.vertices.len() > 2 && x1 == x2 && y1 == y2,
+            _ => false
+        }
+    }
 
-        for vertex in self.vertices[1..].iter() {
-            let Vec2(x, y) = vertex;
-            writeln!(file, "{} {} lineto", x, y)
-                .expect("failed to write lineto");
+    /// Area enclosed by a closed polyline
+    pub fn area(&self) -> f64 {
+        Self::signed_area(&self.vertices).abs() / 2.0
+    }
+
+    pub fn is_counterclockwise(&self) -> bool {
+        Self::signed_area(&self.vertices) > 0.0
+    }
+
+    /// The same path traced the other way
+    pub fn reversed(&self) -> Self {
+        let mut vertices = self.vertices.clone();
+        vertices.reverse();
+
+        Self {
+            vertices
+        }
+    }
+
+    /// Twice the signed area of a closed polyline. Positive when the
+    /// vertices go counterclockwise.
+    fn signed_area(vertices: &[Vec2]) -> f64 {
+        let n = vertices.len();
+        (0..n)
+            .map(|i| vertices[i].cross(vertices[(i + 1) % n]))
+            .sum()
+    }
+
+    /// Offset a closed polyline by `distance`. Positive distances grow the
+    /// shape outward, negative ones shrink it inward. Open polylines are
+    /// returned unchanged.
+    ///
+    /// Each edge moves along its normal. Where the offset edges overlap at a
+    /// corner, the corner is their intersection. Where they leave a gap, the
+    /// gap is filled according to `join`.
+    pub fn offset(&self, distance: f64, join: Join) -> Self {
+        // The miter would stick out more than this many times the offset
+        // distance, so round the corner instead.
+        const MITER_LIMIT: f64 = 4.0;
+        // Angle between points on round joins
+        const ARC_STEP: f64 = PI / 16.0;
+        // Points closer than this are duplicates
+        const EPSILON: f64 = 1e-12;
+
+        if !self.is_closed() || distance == 0.0 {
+            return Self {
+                vertices: self.vertices.clone()
+            };
+        }
+
+        // Drop the repeated first vertex and any zero-length edges
+        let mut points: Vec<Vec2> = Vec::new();
+        for v in self.vertices[..self.vertices.len() - 1].iter() {
+            let is_duplicate = points
+                .last()
+                .is_some_and(|last| v.sub(*last).length() < EPSILON);
+            if !is_duplicate {
+                points.push(*v);
+            }
+        }
+        while points.len() > 1 &&
+                points[0].sub(points[points.len() - 1]).length() < EPSILON {
+            points.pop();
+        }
+
+        let n = points.len();
+        if n < 3 {
+            return Self {
+                vertices: self.vertices.clone()
+            };
+        }
+
+        // perpendicular() points to the right of each edge, which is
+        // outside for a counterclockwise polygon
+        let orientation = Self::signed_area(&points).signum();
+        let side = orientation * distance.signum();
+        let amount = distance.abs();
+
+        let mut result = Vec::new();
+        for i in 0..n {
+            let prev = points[(i + n - 1) % n];
+            let current = points[i];
+            let next = points[(i + 1) % n];
+
+            let edge_in = current.sub(prev).normalize();
+            let edge_out = next.sub(current).normalize();
+            let normal_in = edge_in.perpendicular().scale(side);
+            let normal_out = edge_out.perpendicular().scale(side);
+
+            // The offset edges leave a gap when the corner turns away from
+            // the side being offset toward. An edge that doubles back on
+            // itself leaves a gap all the way around its tip.
+            let turn = edge_in.cross(edge_out);
+            let cos_angle = normal_in.dot(normal_out);
+            let reverses = turn.abs() <= EPSILON && cos_angle < 0.0;
+            let leaves_gap = turn * side > EPSILON || reverses;
+
+            let miter_ratio = (2.0 / (1.0 + cos_angle).max(EPSILON)).sqrt();
+            let too_sharp = miter_ratio > MITER_LIMIT;
+
+            if leaves_gap && (join == Join::Round || too_sharp) {
+                let start = normal_in.1.atan2(normal_in.0);
+                let mut sweep = normal_out.1.atan2(normal_out.0) - start;
+                if reverses {
+                    // Go around the tip, which is in the direction of the
+                    // incoming edge
+                    sweep = PI * side;
+                } else if sweep > PI {
+                    // Otherwise go the short way around the corner
+                    sweep -= 2.0 * PI;
+                } else if sweep < -PI {
+                    sweep += 2.0 * PI;
+                }
+
+                let steps = ((sweep.abs() / ARC_STEP).ceil() as usize).max(1);
+                for j in 0..=steps {
+                    let angle = start + sweep * (j as f64) / (steps as f64);
+                    let direction = Vec2(angle.cos(), angle.sin());
+                    result.push(current.translate(direction.scale(amount)));
+                }
+            } else if too_sharp {
+                // The edges would meet far past the corner, so end each
+                // edge at the corner instead
+                result.push(current.translate(normal_in.scale(amount)));
+                result.push(current.translate(normal_out.scale(amount)));
+            } else {
+                // Intersection of the two offset edges
+                let bisector = normal_in.translate(normal_out);
+                let miter = bisector.scale(amount / (1.0 + cos_angle));
+                result.push(current.translate(miter));
+            }
+        }
+
+        Self::new(&result, true)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    /// The plate the slices stand in, which lies flat
+    Base,
+    /// Slices through the vertical axis of the model, like spokes
+    Radial,
+    /// Strips bent into circles around the vertical axis
+    Ring,
+    /// Slices in one of several families at any angle, numbered from 0
+    Family(usize),
+}
+
+/// Which slice this is, e.g. X3 is the third slice perpendicular to the
+/// y-axis (the slices at y = constant that run along the x-axis).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SliceId {
+    pub axis: Axis,
+    pub index: usize,
+}
+
+impl SliceId {
+    pub fn new(axis: Axis, index: usize) -> Self {
+        Self {
+            axis,
+            index
         }
-        writeln!(file, "stroke").expect("failed to write stroke");
     }
 }
 
+impl fmt::Display for SliceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let axis = match self.axis {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Radial => "R",
+            Axis::Ring => "O",
+            // Families are lettered from A
+            Axis::Family(family) => {
+                let letter = (b'A' + family as u8) as char;
+                return write!(f, "{}{}", letter, self.index + 1);
+            },
+            // There is only ever one
+            Axis::Base => return write!(f, "Base"),
+        };
+
+        // Number the slices from 1 for the people assembling them
+        write!(f, "{}{}", axis, self.index + 1)
+    }
+}
+
+/// A slit along with the slice that slides into it.
+pub struct Slit {
+    path: Polyline,
+    crossing: SliceId,
+}
+
+impl Slit {
+    pub fn new(path: Polyline, crossing: SliceId) -> Self {
+        Self {
+            path,
+            crossing
+        }
+    }
+
+    pub fn path(&self) -> &Polyline {
+        &self.path
+    }
+
+    pub fn crossing(&self) -> SliceId {
+        self.crossing
+    }
+
+    fn map_path(&self, f: impl Fn(&Polyline) -> Polyline) -> Self {
+        Self::new(f(&self.path), self.crossing)
+    }
+}
+
+/// A piece to cut out. The outlines are closed and together bound the
+/// piece the even-odd way: an outline inside another one is a hole, and
+/// one inside a hole is an island.
 pub struct Slice {
-    outline: Polyline,
-    slits: Vec<Polyline>,
+    id: SliceId,
+    outlines: Vec<Polyline>,
+    slits: Vec<Slit>,
 }
 
 impl Slice {
-    pub fn new(outline: Polyline, slits: Vec<Polyline>) -> Self {
-
+    pub fn new(id: SliceId, outlines: Vec<Polyline>, slits: Vec<Slit>) -> Self {
         Self {
-            outline,
+            id,
+            outlines,
             slits,
         }
     }
 
+    pub fn id(&self) -> SliceId {
+        self.id
+    }
+
     pub fn get_bbox(&self) -> BBox {
-        self.outline.get_bbox()
+        let mut bbox = BBox::new();
+        for v in self.outlines.iter().flat_map(|outline| outline.vertices()) {
+            bbox.add_point(v);
+        }
+
+        bbox
     }
 
-    pub fn translate(&self, amount: Vec2) -> Self {
-        let outline = self.outline.translate(amount);
+    pub fn outlines(&self) -> &[Polyline] {
+        &self.outlines
+    }
+
+    pub fn slits(&self) -> &[Slit] {
+        &self.slits
+    }
+
+    /// Whether a point is in the material of the piece
+    pub fn contains(&self, point: Vec2) -> bool {
+        let count = self.outlines
+            .iter()
+            .filter(|outline| outline.contains(point))
+            .count();
+
+        count % 2 == 1
+    }
+
+    /// Whether an outline bounds a hole, meaning it is inside an odd number
+    /// of the other outlines
+    fn is_hole(&self, index: usize) -> bool {
+        let point = self.outlines[index].vertices()[0];
+        let count = self.outlines
+            .iter()
+            .enumerate()
+            .filter(|(i, outline)| *i != index && outline.contains(point))
+            .count();
+
+        count % 2 == 1
+    }
+
+    /// Area of the material, not counting holes
+    pub fn area(&self) -> f64 {
+        (0..self.outlines.len())
+            .map(|i| {
+                let area = self.outlines[i].area();
+                if self.is_hole(i) { -area } else { area }
+            })
+            .sum()
+    }
+
+    fn map_paths(&self, f: impl Fn(&Polyline) -> Polyline) -> Self {
+        let outlines = self.outlines.iter().map(&f).collect();
         let slits = self.slits
             .iter()
-            .map(|slit| slit.translate(amount))
+            .map(|slit| slit.map_path(&f))
             .collect();
 
-        Self::new(outline, slits) 
+        Self::new(self.id, outlines, slits)
+    }
+
+    pub fn translate(&self, amount: Vec2) -> Self {
+        self.map_paths(|path| path.translate(amount))
     }
 
     pub fn scale(&self, factor: f64) -> Self {
-        let outline = self.outline.scale(factor);
+        self.map_paths(|path| path.scale(factor))
+    }
+
+    /// Turn the slice 90 degrees counterclockwise about the origin
+    pub fn rotate_quarter(&self) -> Self {
+        self.map_paths(|path| path.rotate_quarter())
+    }
+
+    /// Compensate for material the cutter removes. The material grows by
+    /// half the kerf on every edge, so outer outlines grow and holes and
+    /// notches shrink, and everything comes out the intended size. Slits
+    /// that are single cut lines are left alone.
+    pub fn compensate_kerf(&self, kerf: f64, join: Join) -> Self {
+        let half_kerf = kerf / 2.0;
+        let outlines = (0..self.outlines.len())
+            .map(|i| {
+                let distance = if self.is_hole(i) { -half_kerf } else { half_kerf };
+                self.outlines[i].offset(distance, join)
+            })
+            .collect();
         let slits = self.slits
             .iter()
-            .map(|slit| slit.scale(factor))
+            .map(|slit| slit.map_path(|path| path.offset(-half_kerf, join)))
             .collect();
 
-        Self::new(outline, slits) 
+        Self::new(self.id, outlines, slits)
     }
+}
 
-    pub fn write_postscript(&self, file: &mut File, draw_bbox: bool) {
-        if draw_bbox {
-            self.outline.get_bbox().get_outline().write_postscript(file);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        self.outline.write_postscript(file);
-        for slit in self.slits.iter() {
-            slit.write_postscript(file);
-        }
+    fn square(left: f64, bottom: f64, size: f64) -> Polyline {
+        Polyline::new(&[
+            Vec2(left, bottom),
+            Vec2(left + size, bottom),
+            Vec2(left + size, bottom + size),
+            Vec2(left, bottom + size),
+        ], true)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn same_path(a: &Polyline, b: &Polyline) -> bool {
+        a.vertices().len() == b.vertices().len() &&
+            a.vertices().iter().zip(b.vertices()).all(|(p, q)| {
+                p.sub(*q).length() < 1e-9
+            })
+    }
+
+    #[test]
+    fn offset_grows_and_shrinks() {
+        let grown = square(0.0, 0.0, 1.0).offset(0.1, Join::Miter);
+        assert!(close(grown.area(), 1.44));
+        assert!(same_path(&grown, &square(-0.1, -0.1, 1.2)));
+
+        let shrunk = square(0.0, 0.0, 1.0).offset(-0.1, Join::Miter);
+        assert!(close(shrunk.area(), 0.64));
     }
-}
 
+    #[test]
+    fn offset_ignores_orientation() {
+        let clockwise = square(0.0, 0.0, 1.0).reversed();
+        assert!(close(clockwise.offset(0.1, Join::Miter).area(), 1.44));
+    }
+
+    #[test]
+    fn round_join() {
+        // Quarter circles in the corners come close to a full circle
+        let area = square(0.0, 0.0, 1.0).offset(0.1, Join::Round).area();
+        assert!(area > 1.4 && area < 1.0 + 0.4 + PI * 0.01);
+    }
+
+    #[test]
+    fn offset_leaves_open_paths() {
+        let path = Polyline::new(&[Vec2(0.0, 0.0), Vec2(1.0, 0.0)], false);
+        assert!(same_path(&path.offset(0.1, Join::Miter), &path));
+    }
+
+    #[test]
+    fn kerf_grows_outlines_and_shrinks_holes_and_notches() {
+        let id = SliceId::new(Axis::X, 0);
+        let notch = Slit::new(square(0.1, 0.1, 0.1), SliceId::new(Axis::Y, 0));
+        let cut = Slit::new(
+            Polyline::new(&[Vec2(0.9, 0.0), Vec2(0.9, 0.5)], false),
+            SliceId::new(Axis::Y, 1));
+        let slice = Slice::new(
+            id,
+            vec![square(0.0, 0.0, 1.0), square(0.4, 0.4, 0.2)],
+            vec![notch, cut]);
+
+        let compensated = slice.compensate_kerf(0.02, Join::Miter);
+        assert!(close(compensated.outlines()[0].area(), 1.02 * 1.02));
+        assert!(close(compensated.outlines()[1].area(), 0.18 * 0.18));
+        assert!(close(compensated.slits()[0].path().area(), 0.08 * 0.08));
+        assert!(same_path(
+            compensated.slits()[1].path(),
+            slice.slits()[1].path()));
+    }
+}
@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use crate::expression::Expression;
+use crate::heights::Height1D;
+
+// More slices than this makes a template nobody wants to cut out
+const MAX_SLICES: usize = 127;
+
+/// Where the slices along one axis go. Positions run from 0 to 1 across the
+/// model and must be strictly between the two.
+pub enum SlicePositions {
+    /// Evenly spaced slices
+    Even(usize),
+    /// Slices at exactly these positions
+    List(Vec<f64>),
+    /// Slices at `spacing(t)` for evenly spaced t, so `x^2` bunches them up
+    /// towards 0
+    Spaced {
+        count: usize,
+        spacing: Expression,
+    },
+}
+
+impl SlicePositions {
+    /// The 2^(n + 1) - 1 evenly spaced slices of a slice resolution
+    pub fn from_resolution(slice_res: u32) -> Self {
+        Self::Even((1 << (slice_res + 1)) - 1)
+    }
+
+    /// The positions in order from 0 to 1
+    pub fn positions(&self) -> Vec<f64> {
+        let even = |count: usize| {
+            (1..=count).map(move |i| i as f64 / (count + 1) as f64)
+        };
+
+        match self {
+            Self::Even(count) => even(*count).collect(),
+            Self::List(positions) => positions.clone(),
+            Self::Spaced { count, spacing } => {
+                even(*count).map(|t| spacing.compute(t)).collect()
+            },
+        }
+    }
+
+    /// How many slices there are, without working out where they go
+    fn count(&self) -> usize {
+        match self {
+            Self::Even(count) | Self::Spaced { count, .. } => *count,
+            Self::List(positions) => positions.len(),
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        // Checked before the positions are made, so a huge count doesn't
+        // have to fit in memory first
+        if self.count() == 0 {
+            return Err(String::from("needs at least one slice"));
+        }
+
+        if self.count() > MAX_SLICES {
+            return Err(format!("at most {} slices are supported", MAX_SLICES));
+        }
+
+        let positions = self.positions();
+        if let Some(bad) = positions.iter().find(|&&p| !(p > 0.0 && p < 1.0)) {
+            return Err(format!(
+                "slice position {} is not strictly between 0 and 1", bad));
+        }
+
+        if positions.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(String::from(
+                "slice positions must go up from one slice to the next"));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SlicePositions {
+    type Err = String;
+
+    /// A count like "5", a list of positions like "0.2,0.5,0.6", or a count
+    /// and a spacing formula like "9:x^2"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_count = |text: &str| {
+            text.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("'{}' is not a number of slices", text))
+        };
+
+        let spec = if let Some((count, formula)) = s.split_once(':') {
+            let spacing = Expression::profile(formula)
+                .map_err(|err| format!("invalid spacing '{}': {}", formula, err))?;
+            Self::Spaced {
+                count: parse_count(count)?,
+                spacing,
+            }
+        } else if s.contains(',') || s.contains('.') {
+            let positions = s
+                .split(',')
+                .map(|part| {
+                    part.trim()
+                        .parse::<f64>()
+                        .map_err(|_| format!("'{}' is not a position", part))
+                })
+                .collect::<Result<Vec<f64>, String>>()?;
+            Self::List(positions)
+        } else {
+            Self::Even(parse_count(s)?)
+        };
+
+        spec.check()?;
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(spec: &str) -> Vec<f64> {
+        spec.parse::<SlicePositions>().unwrap().positions()
+    }
+
+    #[test]
+    fn even_count() {
+        assert_eq!(positions("3"), vec![0.25, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn list() {
+        assert_eq!(positions("0.2, 0.5,0.6"), vec![0.2, 0.5, 0.6]);
+    }
+
+    #[test]
+    fn spacing_formula() {
+        assert_eq!(positions("3:x^2"), vec![0.0625, 0.25, 0.5625]);
+    }
+
+    #[test]
+    fn resolution() {
+        assert_eq!(SlicePositions::from_resolution(1).positions().len(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        for spec in ["0", "128", "abc", "0.5,0.2", "0,0.5", "0.5,1", "3:x+"]
+                .iter() {
+            assert!(spec.parse::<SlicePositions>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn huge_count_is_rejected_before_allocating() {
+        for spec in ["3000000000", "3000000000:x"].iter() {
+            let err = spec.parse::<SlicePositions>().err().unwrap();
+            assert!(err.contains("at most"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use crate::backend::{Backend, Style};
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+
+pub struct PostScriptBackend<W: Write> {
+    out: W,
+    page_number: usize,
+}
+
+impl<W: Write> PostScriptBackend<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            page_number: 0,
+        }
+    }
+
+    /// DSC comments describing the document. The bounding box is taken
+    /// from the first page.
+    fn write_header(&mut self, width: f64, height: f64) -> io::Result<()> {
+        writeln!(self.out, "%!PS-Adobe-3.0")?;
+        writeln!(
+            self.out,
+            "%%BoundingBox: 0 0 {} {}",
+            width.ceil(),
+            height.ceil())?;
+        writeln!(self.out, "%%Pages: (atend)")?;
+        writeln!(self.out, "%%EndComments")
+    }
+}
+
+impl<W: Write> Backend for PostScriptBackend<W> {
+    fn begin_page(&mut self, width: f64, height: f64) -> io::Result<()> {
+        if self.page_number == 0 {
+            self.write_header(width, height)?;
+        }
+        self.page_number += 1;
+
+        writeln!(self.out, "%%Page: {0} {0}", self.page_number)?;
+        writeln!(
+            self.out,
+            "<< /PageSize [{} {}] >> setpagedevice",
+            width,
+            height)
+    }
+
+    fn draw_polyline(&mut self, polyline: &Polyline, style: Style)
+            -> io::Result<()> {
+        let vertices = polyline.vertices();
+
+        // Don't write individual points.
+        if vertices.len() < 2 {
+            return Ok(());
+        }
+
+        writeln!(self.out, "newpath")?;
+
+        let Vec2(x, y) = vertices[0];
+        writeln!(self.out, "{} {} moveto", x, y)?;
+
+        for Vec2(x, y) in vertices[1..].iter() {
+            writeln!(self.out, "{} {} lineto", x, y)?;
+        }
+        if style.is_cut() {
+            writeln!(self.out, "stroke")
+        } else {
+            let (r, g, b) = style.color();
+            writeln!(
+                self.out,
+                "gsave {} {} {} setrgbcolor stroke grestore",
+                r,
+                g,
+                b)
+        }
+    }
+
+    fn draw_text(&mut self, text: &str, position: Vec2, size: f64, angle: f64)
+            -> io::Result<()> {
+        // Labels are marks, not cuts, so they get the label color
+        let Vec2(x, y) = position;
+        let (r, g, b) = Style::Label.color();
+        writeln!(self.out, "/Helvetica findfont {} scalefont setfont", size)?;
+        writeln!(self.out, "gsave {} {} {} setrgbcolor", r, g, b)?;
+        if angle == 0.0 {
+            writeln!(self.out, "{} {} moveto", x, y)?;
+        } else {
+            writeln!(self.out, "{} {} translate {} rotate", x, y, angle)?;
+            writeln!(self.out, "0 0 moveto")?;
+        }
+        writeln!(self.out, "({}) show grestore", escape_string(text))
+    }
+
+    fn end_page(&mut self) -> io::Result<()> {
+        writeln!(self.out, "showpage")
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        writeln!(self.out, "%%Trailer")?;
+        writeln!(self.out, "%%Pages: {}", self.page_number)?;
+        writeln!(self.out, "%%EOF")?;
+        self.out.flush()
+    }
+}
+
+/// Escape the characters that are special inside PostScript and PDF
+/// string literals.
+pub fn escape_string(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if c == '(' || c == ')' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::draw_piece;
+
+    fn render() -> String {
+        let mut out = Vec::new();
+        draw_piece(&mut PostScriptBackend::new(&mut out)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn document_structure() {
+        let ps = render();
+        let lines: Vec<&str> = ps.lines().collect();
+        assert_eq!(lines[..4], [
+            "%!PS-Adobe-3.0",
+            "%%BoundingBox: 0 0 612 792",
+            "%%Pages: (atend)",
+            "%%EndComments",
+        ]);
+        assert_eq!(lines[4], "%%Page: 1 1");
+        assert_eq!(lines[5], "<< /PageSize [612 792] >> setpagedevice");
+        assert_eq!(lines[lines.len() - 4..], [
+            "showpage",
+            "%%Trailer",
+            "%%Pages: 1",
+            "%%EOF",
+        ]);
+    }
+
+    #[test]
+    fn paths_and_text() {
+        let ps = render();
+        assert_eq!(ps.matches("newpath").count(), 3);
+        assert_eq!(ps.matches("stroke").count(), 3);
+        assert!(ps.contains("72 72 moveto\n144 72 lineto\n"));
+        assert!(ps.contains("108 144 moveto\n108 108 lineto\n"));
+
+        // Cuts are black, and the label and its arrow are marked in blue
+        assert!(ps.contains("72 72 lineto\nstroke\n"));
+        assert!(ps.contains(
+            "92 76 lineto\ngsave 0 0 1 setrgbcolor stroke grestore\n"));
+        assert!(ps.contains(
+            "/Helvetica findfont 10 scalefont setfont\n\
+            gsave 0 0 1 setrgbcolor\n76 76 moveto\n(X1) show grestore\n"));
+    }
+
+    #[test]
+    fn counts_pages() {
+        let mut out = Vec::new();
+        let mut backend = PostScriptBackend::new(&mut out);
+        for _ in 0..2 {
+            backend.begin_page(612.0, 792.0).unwrap();
+            backend.end_page().unwrap();
+        }
+        backend.finish().unwrap();
+
+        let ps = String::from_utf8(out).unwrap();
+        assert_eq!(ps.matches("%!PS").count(), 1);
+        assert!(ps.contains("%%Page: 2 2\n"));
+        assert!(ps.ends_with("%%Pages: 2\n%%EOF\n"));
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(escape_string("a(b)\\c"), "a\\(b\\)\\\\c");
+    }
+}
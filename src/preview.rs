@@ -1,6 +1,9 @@
+use std::io;
+
+use crate::domain::Domain;
 use crate::mesh::{Mesh, Face};
 use crate::heights::Height2D;
-use crate::geom::Vec3;
+use crate::geom::{Vec2, Vec3};
 
 type VertexSlot = Option<usize>;
 
@@ -24,6 +27,10 @@ pub struct SurfacePreview {
 
     // The math surface to model
     surf: Box<dyn Height2D>,
+
+    // Width, depth and height of the mesh, where the surface is at 1. The
+    // width is always 1.
+    size: Vec3,
 }
 
 impl SurfacePreview {
@@ -46,9 +53,23 @@ impl SurfacePreview {
             back: Self::allocate_slots(m, p),
             left: Self::allocate_slots(n, p),
             surf,
+            size: Vec3(1.0, 1.0, 1.0),
         }
     }
 
+    /// Stretch the mesh to be `depth` along y and `height` tall, in units
+    /// of its width
+    pub fn with_size(mut self, depth: f64, height: f64) -> Self {
+        self.size = Vec3(1.0, depth, height);
+        self
+    }
+
+    /// Add a vertex of the unit cube, stretched to the size of the mesh
+    fn add_vertex(&mut self, Vec3(x, y, z): Vec3) -> usize {
+        let Vec3(_, depth, height) = self.size;
+        self.mesh.add_vertex(Vec3(x, y * depth, z * height))
+    }
+
     pub fn generate_mesh(&mut self) {
         self.populate_top();
         self.populate_bottom();
@@ -83,7 +104,7 @@ impl SurfacePreview {
                 let z = self.surf.compute_clamped(x, y);
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.top[idx] = Some(vertex_index);
             }
@@ -135,7 +156,7 @@ impl SurfacePreview {
                 let y = Self::to_coordinate(self.length - 1 - j, self.length);
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.bottom[idx] = Some(vertex_index);
             }
@@ -190,7 +211,7 @@ impl SurfacePreview {
                 let z = factor * h;
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.front[idx] = Some(vertex_index);
             }
@@ -216,7 +237,7 @@ impl SurfacePreview {
                 let z = factor * h;
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.right[idx] = Some(vertex_index);
             }
@@ -242,7 +263,7 @@ impl SurfacePreview {
                 let z = factor * h;
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.back[idx] = Some(vertex_index);
             }
@@ -268,7 +289,7 @@ impl SurfacePreview {
                 let z = factor * h;
 
                 let vertex = Vec3(x, y, z);
-                let vertex_index = self.mesh.add_vertex(vertex);
+                let vertex_index = self.add_vertex(vertex);
                 let idx = Self::to_index_1d(i, j, self.width);
                 self.left[idx] = Some(vertex_index);
             }
@@ -428,7 +449,117 @@ impl SurfacePreview {
         }
     }
 
-    pub fn save_obj_file(&self, fname: &str) {
-        self.mesh.save_obj_file(fname);
+    pub fn save_obj_file(&self, fname: &str) -> io::Result<()> {
+        self.mesh.save_obj_file(fname)
+    }
+}
+
+/// A preview of a surface standing on a footprint other than the square.
+/// The top is made of rings around the center of the footprint, each a
+/// smaller copy of its edge, and the sides stand on the edge.
+pub struct FootprintPreview {
+    mesh: Mesh,
+    domain: Domain,
+    // Vertices around the edge, rings from the center out to the edge, and
+    // vertices up the sides
+    around: usize,
+    rings: usize,
+    height: usize,
+    surf: Box<dyn Height2D>,
+    // Width, depth and height of the mesh, where the surface is at 1. The
+    // width is always 1.
+    size: Vec3,
+}
+
+impl FootprintPreview {
+    /// The domain must be star shaped, so the rings don't cross
+    pub fn new(
+            domain: Domain,
+            around: usize,
+            rings: usize,
+            height: usize,
+            surf: Box<dyn Height2D>) -> Self {
+        Self {
+            mesh: Mesh::new(),
+            domain,
+            around,
+            rings,
+            height,
+            surf,
+            size: Vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Stretch the mesh to be `depth` along y and `height` tall, in units
+    /// of its width
+    pub fn with_size(mut self, depth: f64, height: f64) -> Self {
+        self.size = Vec3(1.0, depth, height);
+        self
+    }
+
+    pub fn generate_mesh(&mut self) {
+        let center = self.domain.center();
+        let edge = self.domain.outline(self.around);
+        let count = edge.len();
+        let point = |k: usize, fraction: f64| {
+            center.translate(edge[k % count].sub(center).scale(fraction))
+        };
+
+        // The top, from the center out, and the sides from the top of the
+        // edge down
+        let top_center = self.add_vertex(center, 1.0);
+        let mut rings: Vec<Vec<usize>> = Vec::new();
+        for r in 1..self.rings {
+            let fraction = r as f64 / (self.rings - 1) as f64;
+            let ring = (0..count)
+                .map(|k| self.add_vertex(point(k, fraction), 1.0))
+                .collect();
+            rings.push(ring);
+        }
+        let mut sides: Vec<Vec<usize>> = vec![rings[rings.len() - 1].clone()];
+        for j in (0..self.height - 1).rev() {
+            let factor = j as f64 / (self.height - 1) as f64;
+            let ring = edge
+                .iter()
+                .map(|&p| self.add_vertex(p, factor))
+                .collect();
+            sides.push(ring);
+        }
+
+        for k in 0..count {
+            let next = (k + 1) % count;
+            self.mesh.add_triangle(top_center, rings[0][k], rings[0][next]);
+        }
+        for strips in [rings.as_slice(), sides.as_slice()].iter() {
+            for pair in strips.windows(2) {
+                let (inner, outer) = (&pair[0], &pair[1]);
+                for k in 0..count {
+                    let next = (k + 1) % count;
+                    self.mesh.add_triangle(inner[k], outer[k], outer[next]);
+                    self.mesh.add_triangle(inner[k], outer[next], inner[next]);
+                }
+            }
+        }
+
+        let normal = self.mesh.add_normal(Vec3(0.0, 0.0, -1.0));
+        let bottom_center = self.add_vertex(center, 0.0);
+        let bottom = &sides[sides.len() - 1];
+        for k in 0..count {
+            let next = (k + 1) % count;
+            let face = Face::new(bottom_center, bottom[next], bottom[k], normal);
+            self.mesh.add_face(face);
+        }
+    }
+
+    /// Add a vertex above `point`, `factor` of the way up to the surface
+    fn add_vertex(&mut self, point: Vec2, factor: f64) -> usize {
+        let Vec2(x, y) = point;
+        let Vec3(_, depth, height) = self.size;
+        let z = factor * self.surf.compute_clamped(x, y);
+        self.mesh.add_vertex(Vec3(x, y * depth, z * height))
+    }
+
+    pub fn save_obj_file(&self, fname: &str) -> io::Result<()> {
+        self.mesh.save_obj_file(fname)
     }
 }
@@ -1,92 +1,343 @@
-use std::fs::File;
-use std::io::Write;
+use std::io;
 
+use crate::backend::{Backend, Style};
 use crate::geom::Vec2;
-use crate::polylines::{BBox, Slice};
+use crate::layout::{Layout, LayoutReport, Page, Placement};
+use crate::page::PageFormat;
+use crate::polylines::{BBox, Join, Polyline, Slice};
 use crate::slicer::Slicer;
+use crate::validation::Limits;
 
-pub struct Printer {
-    cursor: Vec2,
-    file: File,
-    scale: f64,
-    draw_bboxes: bool
-}
+// Font sizes in points
+const LABEL_SIZE: f64 = 10.0;
+const SLIT_LABEL_SIZE: f64 = 6.0;
 
-impl Printer {
-    // 72 points per inch
-    const INCH: f64 = 72.0;
+// Approximate width of a Helvetica capital or digit relative to the font
+// size. Backends don't report text metrics, so this is close enough to keep
+// labels inside their pieces.
+const CHAR_WIDTH: f64 = 0.7;
 
-    // Letter-sized paper with 1/2 inch margins
-    const WIDTH: f64 = 8.5 * Self::INCH;
-    const HEIGHT: f64 = 11.0 * Self::INCH;
-    const MARGIN: f64 = 0.5 * Self::INCH;
+// Space between labels and nearby geometry in points
+const LABEL_GAP: f64 = 2.0;
 
-    pub fn new(fname: &str, scale: f64, draw_bboxes: bool) -> Self {
-        let file = File::create(fname).expect("Failed to open output file");
+/// Lays out the slices and draws them with any backend.
+pub struct Printer<B: Backend> {
+    backend: B,
+    page_format: PageFormat,
+    scale: f64,
+    draw_bboxes: bool,
+    draw_labels: bool,
+    kerf: f64,
+    join: Join,
+    spacing: f64,
+    allow_rotation: bool,
+    limits: Option<Limits>,
+}
 
+impl<B: Backend> Printer<B> {
+    pub fn new(backend: B, scale: f64, draw_bboxes: bool) -> Self {
         Self {
-            cursor: Vec2(Self::MARGIN, Self::MARGIN),
-            file,
+            backend,
+            page_format: PageFormat::default(),
             scale,
-            draw_bboxes
+            draw_bboxes,
+            draw_labels: true,
+            kerf: 0.0,
+            join: Join::Miter,
+            spacing: 0.0,
+            allow_rotation: false,
+            limits: None,
         }
     }
 
-    fn next_row(&mut self, bbox: &BBox) {
-        self.cursor = self.cursor.translate(Vec2(0.0, bbox.height()));
+    pub fn with_page_format(mut self, page_format: PageFormat) -> Self {
+        self.page_format = page_format;
+        self
     }
 
-    fn next_column(&mut self, bbox: &BBox) {
-        let Vec2(x, _) = self.cursor;
-        self.cursor = Vec2(x + bbox.width(), Self::MARGIN);
+    /// Compensate for a cutter that removes `kerf` points of material.
+    pub fn with_kerf(mut self, kerf: f64, join: Join) -> Self {
+        self.kerf = kerf;
+        self.join = join;
+        self
     }
 
-    pub fn next_page(&mut self) {
-        writeln!(self.file, "showpage").expect("could not write showpage");
-        //writeln!(self.file, "0 0 1 setrgbcolor").expect("Could not set color");
-        self.cursor = Vec2(Self::MARGIN, Self::MARGIN);
+    /// Leave `spacing` points between pieces and optionally let them turn
+    /// a quarter turn to fit more on each page.
+    pub fn with_packing(mut self, spacing: f64, allow_rotation: bool) -> Self {
+        self.spacing = spacing;
+        self.allow_rotation = allow_rotation;
+        self
     }
 
-    fn draw_slice_at_cursor(&mut self, slice: &Slice) {
-        slice.translate(self.cursor)
-            .write_postscript(&mut self.file, self.draw_bboxes);
+    /// Check every piece against these limits before printing. The report
+    /// lists any problems found.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
 
-        self.next_row(&slice.get_bbox());
+    /// Label each piece with its ID and each slit with the ID of the piece
+    /// that slides into it. On by default.
+    pub fn with_labels(mut self, draw_labels: bool) -> Self {
+        self.draw_labels = draw_labels;
+        self
     }
 
-    pub fn init(&mut self) {
-        //writeln!(self.file, "0 0 1 setrgbcolor").expect("Could not set color");
+    fn print_page(&mut self, page: &Page) -> io::Result<()> {
+        let PageFormat { width, height, .. } = self.page_format;
+        self.backend.begin_page(width, height)?;
+
+        for placement in page.placements.iter() {
+            self.backend.begin_slice(placement.offset)?;
+
+            let slice = placement.placed_slice();
+            if self.draw_bboxes {
+                let bbox = slice.get_bbox().get_outline();
+                self.backend.draw_polyline(&bbox, Style::BoundingBox)?;
+            }
+
+            for outline in slice.outlines().iter() {
+                self.backend.draw_polyline(outline, Style::Outline)?;
+            }
+            for slit in slice.slits().iter() {
+                self.backend.draw_polyline(slit.path(), Style::Slit)?;
+            }
+
+            if self.draw_labels {
+                self.print_labels(placement)?;
+            }
+
+            self.backend.end_slice()?;
+        }
+
+        self.backend.end_page()
     }
 
-    pub fn print_slice(&mut self, slice: &Slice) {
-        let scaled = slice.scale(self.scale * Self::INCH);
-        let bbox = scaled.get_bbox();
-        let Vec2(x, y) = self.cursor;
+    /// Write the slice ID inside the piece followed by an arrow pointing to
+    /// the top edge. The side with the labels is the front: X slices face
+    /// -y and Y slices face -x once assembled. Labels are placed in the
+    /// slice's own coordinates and turn with it.
+    fn print_labels(&mut self, placement: &Placement) -> io::Result<()> {
+        let slice = &placement.slice;
+        let angle = placement.angle();
+        let label = slice.id().to_string();
+        let label_width = text_width(&label, LABEL_SIZE);
+        let arrow_width = 0.6 * LABEL_SIZE;
+        let width = label_width + LABEL_GAP + arrow_width;
+
+        if let Some(corner) = find_label_spot(slice, width, LABEL_SIZE) {
+            let position = placement.to_page(corner);
+            self.backend.draw_text(&label, position, LABEL_SIZE, angle)?;
 
-        if y + bbox.height() <= Self::HEIGHT - Self::MARGIN {
-            self.draw_slice_at_cursor(&scaled);
-        } else if x + 2.0 * bbox.width() <= Self::WIDTH - Self::MARGIN {
-            self.next_column(&bbox);
-            self.draw_slice_at_cursor(&scaled);
-        } else {
-            self.next_page();
-            self.draw_slice_at_cursor(&scaled);
+            let Vec2(x, y) = corner.translate(Vec2(label_width + LABEL_GAP, 0.0));
+            let arrow = Polyline::new(&[
+                Vec2(x, y),
+                Vec2(x + arrow_width, y),
+                Vec2(x + arrow_width / 2.0, y + LABEL_SIZE),
+            ], true);
+            let arrow = placement.polyline_to_page(&arrow);
+            self.backend.draw_polyline(&arrow, Style::Label)?;
         }
+
+        for (index, slit) in slice.slits().iter().enumerate() {
+            let label = slit.crossing().to_string();
+            let width = text_width(&label, SLIT_LABEL_SIZE);
+            if let Some(corner) = find_slit_label_spot(slice, index, width) {
+                let position = placement.to_page(corner);
+                self.backend.draw_text(
+                    &label, position, SLIT_LABEL_SIZE, angle)?;
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn print_slices(&mut self, slicer: &Slicer) {
-        self.init();
+    pub fn print_slices(&mut self, slicer: &Slicer)
+            -> io::Result<LayoutReport> {
+        let (pages, report) = Layout::new(self.scale, self.page_format)
+            .with_kerf(self.kerf, self.join)
+            .with_spacing(self.spacing)
+            .with_rotation(self.allow_rotation)
+            .with_limits(self.limits)
+            .lay_out_slices(slicer);
 
-        for slice in slicer.make_x_slices().iter() {
-            self.print_slice(slice);
+        for page in pages.iter() {
+            self.print_page(page)?;
         }
 
-        self.next_page();
+        self.backend.finish()?;
+        Ok(report)
+    }
+}
+
+fn text_width(text: &str, size: f64) -> f64 {
+    CHAR_WIDTH * size * (text.chars().count() as f64)
+}
+
+/// Find the bottom left corner of a `width` x `height` box that fits inside
+/// the slice without touching any slits. Rows are tried from the bottom up,
+/// and each row from the middle outwards.
+fn find_label_spot(slice: &Slice, width: f64, height: f64) -> Option<Vec2> {
+    const COLUMNS: usize = 16;
 
-        for slice in slicer.make_y_slices().iter() {
-            self.print_slice(slice);
+    let bbox = slice.get_bbox();
+    let Vec2(left, bottom) = bbox.bottom_left();
+    let top = bottom + bbox.height();
+
+    let slit_boxes = slit_boxes(slice);
+    let x_min = left + LABEL_GAP;
+    let x_max = left + bbox.width() - LABEL_GAP - width;
+    if x_max < x_min {
+        return None;
+    }
+
+    // Candidate x positions ordered by distance from the middle
+    let mut xs: Vec<f64> = (0..=COLUMNS)
+        .map(|i| x_min + (x_max - x_min) * (i as f64) / (COLUMNS as f64))
+        .collect();
+    let middle = (x_min + x_max) / 2.0;
+    xs.sort_by(|a, b| {
+        (a - middle).abs().partial_cmp(&(b - middle).abs()).unwrap()
+    });
+
+    let mut y = bottom + LABEL_GAP;
+    while y + height + LABEL_GAP <= top {
+        for x in xs.iter() {
+            let corner = Vec2(*x, y);
+            if is_clear(slice, &slit_boxes, corner, width, height) {
+                return Some(corner);
+            }
         }
 
-        self.next_page();
+        y += height / 2.0;
+    }
+
+    None
+}
+
+/// Find the bottom left corner of a label `width` wide for slit `index`,
+/// beside the middle of the slit. The right side is tried first, then the
+/// left. Labels that don't fit on either side are left off, rather than
+/// drawn over the outline or another slit.
+fn find_slit_label_spot(slice: &Slice, index: usize, width: f64)
+        -> Option<Vec2> {
+    let mut slit_boxes = slit_boxes(slice);
+    let bbox = slit_boxes.remove(index);
+    let Vec2(left, _) = bbox.bottom_left();
+    let right = left + bbox.width();
+    let Vec2(_, middle) = bbox.center();
+    let y = middle - SLIT_LABEL_SIZE / 2.0;
+
+    // The gap around the label keeps it clear of its own slit
+    [right + LABEL_GAP, left - LABEL_GAP - width]
+        .iter()
+        .map(|&x| Vec2(x, y))
+        .find(|&corner| {
+            is_clear(slice, &slit_boxes, corner, width, SLIT_LABEL_SIZE)
+        })
+}
+
+fn slit_boxes(slice: &Slice) -> Vec<BBox> {
+    slice.slits()
+        .iter()
+        .map(|slit| slit.path().get_bbox())
+        .collect()
+}
+
+/// Whether a `width` x `height` box with its bottom left corner at `corner`
+/// and a gap all around it fits inside the slice without touching any of
+/// `slit_boxes`
+fn is_clear(
+        slice: &Slice,
+        slit_boxes: &[BBox],
+        corner: Vec2,
+        width: f64,
+        height: f64) -> bool {
+    let Vec2(x, y) = corner;
+    let mut label_box = BBox::new();
+    label_box.add_point(&Vec2(x - LABEL_GAP, y - LABEL_GAP));
+    label_box.add_point(&Vec2(x + width + LABEL_GAP, y + height + LABEL_GAP));
+
+    fits_inside(slice, &label_box) &&
+        !slit_boxes.iter().any(|slit| slit.overlaps(&label_box))
+}
+
+/// Check that a box is inside the material of a slice: its corners must be
+/// inside and no part of any outline may poke into it.
+fn fits_inside(slice: &Slice, label_box: &BBox) -> bool {
+    let outline_box = label_box.get_outline();
+    let corners_inside = outline_box.vertices()
+        .iter()
+        .all(|corner| slice.contains(*corner));
+    let outline_pokes_in = slice.outlines()
+        .iter()
+        .flat_map(|outline| outline.vertices())
+        .any(|v| outline_box.contains(*v));
+
+    corners_inside && !outline_pokes_in
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polylines::{Axis, SliceId, Slit};
+
+    /// A piece 100 x 40 points with slits from the top down to the middle
+    /// at each of `xs`
+    fn piece(xs: &[f64]) -> Slice {
+        let outline = Polyline::new(&[
+            Vec2(0.0, 0.0),
+            Vec2(100.0, 0.0),
+            Vec2(100.0, 40.0),
+            Vec2(0.0, 40.0),
+        ], true);
+        let slits = xs
+            .iter()
+            .enumerate()
+            .map(|(j, &x)| {
+                let path =
+                    Polyline::new(&[Vec2(x, 40.0), Vec2(x, 20.0)], false);
+                Slit::new(path, SliceId::new(Axis::Y, j))
+            })
+            .collect();
+        Slice::new(SliceId::new(Axis::X, 0), vec![outline], slits)
+    }
+
+    fn slit_label(xs: &[f64], index: usize) -> Option<(f64, f64)> {
+        let width = text_width("Y1", SLIT_LABEL_SIZE);
+        find_slit_label_spot(&piece(xs), index, width)
+            .map(|Vec2(x, y)| (x, y))
+    }
+
+    #[test]
+    fn slit_labels_go_right_of_the_slit() {
+        assert_eq!(slit_label(&[50.0], 0), Some((52.0, 27.0)));
+    }
+
+    #[test]
+    fn slit_labels_move_left_when_the_right_is_taken() {
+        // Off the end of the piece
+        let width = text_width("Y1", SLIT_LABEL_SIZE);
+        assert_eq!(slit_label(&[95.0], 0), Some((93.0 - width, 27.0)));
+
+        // Over the next slit
+        assert_eq!(slit_label(&[50.0, 55.0], 0), Some((48.0 - width, 27.0)));
+    }
+
+    #[test]
+    fn slit_labels_are_left_off_when_nothing_fits() {
+        assert_eq!(slit_label(&[45.0, 50.0, 55.0], 1), None);
+        assert_eq!(slit_label(&[4.0, 10.0], 0), None);
+    }
+
+    #[test]
+    fn piece_labels_go_low_and_in_the_middle() {
+        let slice = piece(&[50.0]);
+        let Vec2(x, y) = find_label_spot(&slice, 30.0, LABEL_SIZE).unwrap();
+        assert_eq!((x, y), (35.0, LABEL_GAP));
+
+        // Too small for the label at all
+        assert!(find_label_spot(&slice, 120.0, LABEL_SIZE).is_none());
     }
 }
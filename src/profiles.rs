@@ -0,0 +1,113 @@
+use std::f64::consts::PI;
+
+use crate::heights::Height1D;
+
+/// Straight lines between control points, constant beyond the first and
+/// last points. The points must be sorted by x.
+pub struct PiecewiseLinear {
+    points: Vec<(f64, f64)>,
+}
+
+impl PiecewiseLinear {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        Self { points }
+    }
+}
+
+impl Height1D for PiecewiseLinear {
+    fn compute(&self, x: f64) -> f64 {
+        let (first_x, first_y) = self.points[0];
+        if x <= first_x {
+            return first_y;
+        }
+
+        for pair in self.points.windows(2) {
+            let (x0, y0) = pair[0];
+            let (x1, y1) = pair[1];
+            if x <= x1 {
+                let t = (x - x0) / (x1 - x0);
+                return y0 * (1.0 - t) + y1 * t;
+            }
+        }
+
+        let (_, last_y) = self.points[self.points.len() - 1];
+        last_y
+    }
+}
+
+/// How overlapping peaks combine
+pub enum Combine {
+    Sum,
+    Max,
+}
+
+/// A sharp peak, `height * exp(-sharpness * |x - center|)`
+pub struct Peak {
+    pub center: f64,
+    pub height: f64,
+    pub sharpness: f64,
+}
+
+impl Peak {
+    fn compute(&self, x: f64) -> f64 {
+        self.height * (-self.sharpness * (x - self.center).abs()).exp()
+    }
+}
+
+pub struct Peaks {
+    peaks: Vec<Peak>,
+    combine: Combine,
+}
+
+impl Peaks {
+    pub fn new(peaks: Vec<Peak>, combine: Combine) -> Self {
+        Self { peaks, combine }
+    }
+}
+
+impl Height1D for Peaks {
+    fn compute(&self, x: f64) -> f64 {
+        let heights = self.peaks.iter().map(|peak| peak.compute(x));
+        match self.combine {
+            Combine::Sum => heights.sum(),
+            Combine::Max => heights.fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// `offset + amplitude * sin(2 pi frequency x + phase)`
+pub struct Sine {
+    pub amplitude: f64,
+    pub frequency: f64,
+    pub phase: f64,
+    pub offset: f64,
+    /// Height at exactly x = 0 instead, like a spike at the middle of a
+    /// surface of revolution
+    pub at_zero: Option<f64>,
+}
+
+impl Height1D for Sine {
+    fn compute(&self, x: f64) -> f64 {
+        match self.at_zero {
+            Some(height) if x == 0.0 => height,
+            _ => {
+                let angle = 2.0 * PI * self.frequency * x + self.phase;
+                self.offset + self.amplitude * angle.sin()
+            },
+        }
+    }
+}
+
+/// A bell curve, `height * exp(-sharpness * (x - center)^2)`
+pub struct Gaussian {
+    pub center: f64,
+    pub height: f64,
+    pub sharpness: f64,
+}
+
+impl Height1D for Gaussian {
+    fn compute(&self, x: f64) -> f64 {
+        let shifted = x - self.center;
+        self.height * (-self.sharpness * shifted * shifted).exp()
+    }
+}
@@ -0,0 +1,174 @@
+use crate::models::{self, Model, ModelError, ModelInfo};
+
+/// Models that ship with the program, in the same JSON format as model files
+/// loaded at runtime.
+const BUNDLED: [(&str, &str); 12] = [
+    ("crater_hill", include_str!("../models/crater_hill.json")),
+    ("crater_diamond", include_str!("../models/crater_diamond.json")),
+    ("step_hill", include_str!("../models/step_hill.json")),
+    ("sinc_box", include_str!("../models/sinc_box.json")),
+    ("sine_hill", include_str!("../models/sine_hill.json")),
+    ("peak_rings", include_str!("../models/peak_rings.json")),
+    ("peak_thing", include_str!("../models/peak_thing.json")),
+    ("quad_peak", include_str!("../models/quad_peak.json")),
+    ("nine_peak", include_str!("../models/nine_peak.json")),
+    ("nine_tines", include_str!("../models/nine_tines.json")),
+    ("torus", include_str!("../models/torus.json")),
+    ("bubbles", include_str!("../models/bubbles.json")),
+];
+
+struct Entry {
+    info: ModelInfo,
+    text: String,
+}
+
+/// The models available by name, along with what they say about
+/// themselves. Surfaces are built fresh from the model text on request.
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// A registry with the models that ship with the program
+    pub fn bundled() -> Self {
+        let mut registry = Self::new();
+        for (name, text) in BUNDLED.iter() {
+            registry.register(name, text).expect("bundled models are valid");
+        }
+
+        registry
+    }
+
+    /// Add a model from the text of a model file, replacing any model with
+    /// the same name. The model is checked before it is added.
+    pub fn register(&mut self, name: &str, text: &str)
+            -> Result<(), ModelError> {
+        let info = models::parse_model(name, text)?.info;
+        self.entries.retain(|entry| entry.info.name != name);
+        self.entries.push(Entry {
+            info,
+            text: text.to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// Every model in the order they were registered
+    pub fn models(&self) -> impl Iterator<Item = &ModelInfo> {
+        self.entries.iter().map(|entry| &entry.info)
+    }
+
+    /// Models whose name or description contains `query`, ignoring case
+    pub fn search(&self, query: &str) -> Vec<&ModelInfo> {
+        let query = query.to_lowercase();
+        self.models()
+            .filter(|info| {
+                info.name.to_lowercase().contains(&query) ||
+                    info.description.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    pub fn construct(&self, name: &str) -> Result<Model, ModelError> {
+        let entry = self.find(name)?;
+        models::parse_model(name, &entry.text)
+    }
+
+    fn find(&self, name: &str) -> Result<&Entry, ModelError> {
+        self.entries
+            .iter()
+            .find(|entry| entry.info.name == name)
+            .ok_or_else(|| ModelError::Unknown {
+                name: name.to_string(),
+                suggestion: self.suggest(name).map(String::from),
+            })
+    }
+
+    /// The registered name closest to a misspelled one, if any is close
+    /// enough to be what the user meant
+    fn suggest(&self, name: &str) -> Option<&str> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        self.models()
+            .map(|info| (edit_distance(name, &info.name), info.name.as_str()))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name)
+    }
+}
+
+/// Levenshtein distance: the fewest insertions, deletions and substitutions
+/// that turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Distances from a prefix of a to every prefix of b, one row at a time
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            let deletion = previous[j + 1] + 1;
+            let insertion = current[j] + 1;
+            current.push(substitution.min(deletion).min(insertion));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("torus", "torus"), 0);
+        assert_eq!(edit_distance("tours", "torus"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn unknown_names_suggest_the_closest() {
+        let registry = Registry::bundled();
+        let suggestion = |name: &str| match registry.construct(name) {
+            Err(ModelError::Unknown { suggestion, .. }) => suggestion,
+            _ => panic!("'{}' should be unknown", name),
+        };
+        assert_eq!(suggestion("nine_tine"), Some(String::from("nine_tines")));
+        assert_eq!(suggestion("crater_hil"), Some(String::from("crater_hill")));
+        assert_eq!(suggestion("banana"), None);
+    }
+
+    #[test]
+    fn search_ignores_case() {
+        let registry = Registry::bundled();
+        let names: Vec<&str> = registry
+            .search("TORUS")
+            .iter()
+            .map(|info| info.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["torus"]);
+        assert!(registry.search("no such model").is_empty());
+    }
+
+    #[test]
+    fn register_replaces_and_checks() {
+        let mut registry = Registry::new();
+        let ramp = r#"{ "type": "expression", "formula": "x" }"#;
+        registry.register("ramp", ramp).unwrap();
+        registry.register("ramp", ramp).unwrap();
+        assert_eq!(registry.models().count(), 1);
+        assert!(registry.construct("ramp").is_ok());
+        assert!(registry.register("bad", "{").is_err());
+        assert_eq!(registry.models().count(), 1);
+    }
+}
@@ -0,0 +1,320 @@
+use std::str::FromStr;
+
+use crate::heights::Height2D;
+
+// Samples along each side of the square when fitting the range of a surface
+const FIT_SAMPLES: usize = 129;
+
+/// Which heights of the surface map to the bottom and top of the model
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputRange {
+    /// Heights from 0 to 1, as the surface gives them
+    Unit,
+    /// From the lowest to the highest height sampled over the square
+    Fit,
+    /// From the first height to the second
+    Between(f64, f64),
+}
+
+impl FromStr for InputRange {
+    type Err = String;
+
+    /// "unit", "fit", or a range like "-0.2,1"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unit" => return Ok(Self::Unit),
+            "fit" => return Ok(Self::Fit),
+            _ => {},
+        }
+
+        let bounds = s
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>();
+        match bounds.as_deref() {
+            Ok(&[low, high]) if low < high => Ok(Self::Between(low, high)),
+            Ok(&[_, _]) => Err(String::from("the range must go up")),
+            _ => Err(String::from(
+                "expected unit, fit, or a range like -0.2,1")),
+        }
+    }
+}
+
+/// How heights past the bottom or top of the range are brought back in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit {
+    /// Cut off flat at the bottom and top
+    Clamp,
+    /// Clamp, then ease in and out so the model meets its bottom and top
+    /// smoothly
+    Smoothstep,
+    /// Squash everything into the range along a tanh curve. Nothing is cut
+    /// off, but nothing quite reaches the bottom or top either.
+    Tanh,
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(Self::Clamp),
+            "smoothstep" => Ok(Self::Smoothstep),
+            "tanh" => Ok(Self::Tanh),
+            _ => Err(String::from("expected clamp, smoothstep or tanh")),
+        }
+    }
+}
+
+/// A curve from 0 to 1 that reshapes heights once they are in range
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// h^gamma, which flattens the low ground when gamma is above 1
+    Gamma(f64),
+    /// log(1 + k h) / log(1 + k), which lifts the low ground
+    Log(f64),
+}
+
+impl FromStr for Curve {
+    type Err = String;
+
+    /// "linear", or "gamma:<g>" or "log:<k>" with a positive number
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "linear" {
+            return Ok(Self::Linear);
+        }
+
+        let expected = || String::from("expected linear, gamma:<g> or log:<k>");
+        let (kind, number) = s.split_once(':').ok_or_else(expected)?;
+        let number: f64 = number
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", number))?;
+        if !(number.is_finite() && number > 0.0) {
+            return Err(format!("{} must be positive", kind));
+        }
+
+        match kind {
+            "gamma" => Ok(Self::Gamma(number)),
+            "log" => Ok(Self::Log(number)),
+            _ => Err(expected()),
+        }
+    }
+}
+
+/// The steps that turn the heights a surface gives into heights of the
+/// model from 0 to 1: the range is stretched to fill 0 to 1, anything past
+/// it is limited, the curve reshapes it, and then it is lifted onto the
+/// floor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeightMap {
+    pub range: InputRange,
+    pub limit: Limit,
+    pub curve: Curve,
+    /// Lowest height of the model, as a fraction of its full height
+    pub floor: f64,
+}
+
+impl Default for HeightMap {
+    /// Clamp to 0 to 1 and nothing else, which is what the slicer and
+    /// preview do anyway
+    fn default() -> Self {
+        Self {
+            range: InputRange::Unit,
+            limit: Limit::Clamp,
+            curve: Curve::Linear,
+            floor: 0.0,
+        }
+    }
+}
+
+impl HeightMap {
+    pub fn is_clamp(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The surface with its heights mapped
+    pub fn apply(&self, surface: Box<dyn Height2D>) -> Box<dyn Height2D> {
+        if self.is_clamp() {
+            return surface;
+        }
+
+        let (low, high) = match self.range {
+            InputRange::Unit => (0.0, 1.0),
+            InputRange::Between(low, high) => (low, high),
+            InputRange::Fit => fit(surface.as_ref()),
+        };
+
+        Box::new(MappedSurface {
+            surface,
+            map: *self,
+            low,
+            high,
+        })
+    }
+}
+
+struct MappedSurface {
+    surface: Box<dyn Height2D>,
+    map: HeightMap,
+    low: f64,
+    high: f64,
+}
+
+impl Height2D for MappedSurface {
+    fn compute(&self, x: f64, y: f64) -> f64 {
+        let t = (self.surface.compute(x, y) - self.low) / (self.high - self.low);
+        let t = match self.map.limit {
+            Limit::Clamp => t.clamp(0.0, 1.0),
+            Limit::Smoothstep => {
+                let t = t.clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            },
+            Limit::Tanh => 0.5 + 0.5 * (2.0 * (t - 0.5)).tanh(),
+        };
+        let t = match self.map.curve {
+            Curve::Linear => t,
+            Curve::Gamma(gamma) => t.powf(gamma),
+            Curve::Log(k) => (k * t).ln_1p() / k.ln_1p(),
+        };
+
+        let floor = self.map.floor;
+        floor + t * (1.0 - floor)
+    }
+}
+
+/// The lowest and highest heights of a surface sampled over the square. A
+/// flat surface sits at the bottom of a range 1 tall.
+fn fit(surface: &dyn Height2D) -> (f64, f64) {
+    let step = 1.0 / (FIT_SAMPLES - 1) as f64;
+    let mut low = f64::INFINITY;
+    let mut high = f64::NEG_INFINITY;
+    for i in 0..FIT_SAMPLES {
+        for j in 0..FIT_SAMPLES {
+            let height = surface.compute(i as f64 * step, j as f64 * step);
+            if height.is_finite() {
+                low = low.min(height);
+                high = high.max(height);
+            }
+        }
+    }
+
+    if high <= low {
+        let low = if low.is_finite() { low } else { 0.0 };
+        return (low, low + 1.0);
+    }
+
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Heights from -0.5 up to 1.5 along x, with no value left of `gap`
+    struct Ramp {
+        gap: f64,
+    }
+
+    impl Height2D for Ramp {
+        fn compute(&self, x: f64, _y: f64) -> f64 {
+            if x < self.gap { f64::NAN } else { 2.0 * x - 0.5 }
+        }
+    }
+
+    fn ramp() -> Box<dyn Height2D> {
+        Box::new(Ramp { gap: 0.0 })
+    }
+
+    fn mapped(map: HeightMap, x: f64) -> f64 {
+        map.apply(ramp()).compute(x, 0.5)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!("unit".parse(), Ok(InputRange::Unit));
+        assert_eq!("fit".parse(), Ok(InputRange::Fit));
+        assert_eq!("-0.2, 1".parse(), Ok(InputRange::Between(-0.2, 1.0)));
+        assert_eq!("smoothstep".parse(), Ok(Limit::Smoothstep));
+        assert_eq!("linear".parse(), Ok(Curve::Linear));
+        assert_eq!("gamma:2".parse(), Ok(Curve::Gamma(2.0)));
+        assert_eq!("log: 10".parse(), Ok(Curve::Log(10.0)));
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for &spec in ["", "1", "1,0", "0,0", "a,1", "0,1,2"].iter() {
+            assert!(spec.parse::<InputRange>().is_err(), "{}", spec);
+        }
+        assert!("clip".parse::<Limit>().is_err());
+        for &spec in ["gamma", "gamma:0", "gamma:-1", "gamma:x", "log:inf",
+                "cubic:2"].iter() {
+            assert!(spec.parse::<Curve>().is_err(), "{}", spec);
+        }
+        assert_eq!(
+            "gamma:0".parse::<Curve>(),
+            Err(String::from("gamma must be positive")));
+    }
+
+    #[test]
+    fn default_leaves_surface_alone() {
+        let map = HeightMap::default();
+        assert!(map.is_clamp());
+        assert!(!HeightMap { floor: 0.1, ..map }.is_clamp());
+        assert_close(mapped(map, 1.0), 1.5);
+    }
+
+    #[test]
+    fn ranges_and_floor() {
+        let between = HeightMap {
+            range: InputRange::Between(-0.5, 1.5),
+            ..HeightMap::default()
+        };
+        assert_close(mapped(between, 0.5), 0.5);
+        assert_close(mapped(HeightMap { floor: 0.2, ..between }, 0.5), 0.6);
+
+        let fit = HeightMap { range: InputRange::Fit, ..HeightMap::default() };
+        assert_close(mapped(fit, 0.25), 0.25);
+        assert_close(mapped(fit, 1.0), 1.0);
+
+        // Past the range is clamped
+        let unit = HeightMap { floor: 0.2, ..HeightMap::default() };
+        assert_close(mapped(unit, 0.0), 0.2);
+        assert_close(mapped(unit, 1.0), 1.0);
+    }
+
+    #[test]
+    fn limits_and_curves() {
+        let fit = HeightMap { range: InputRange::Fit, ..HeightMap::default() };
+        let smooth = HeightMap { limit: Limit::Smoothstep, ..fit };
+        assert_close(mapped(smooth, 0.25), 0.15625);
+
+        let tanh = HeightMap { limit: Limit::Tanh, ..fit };
+        assert_close(mapped(tanh, 0.5), 0.5);
+        assert!(mapped(tanh, 0.0) > 0.0 && mapped(tanh, 1.0) < 1.0);
+
+        assert_close(mapped(HeightMap { curve: Curve::Gamma(2.0), ..fit }, 0.5),
+            0.25);
+        let log = HeightMap { curve: Curve::Log(10.0), ..fit };
+        assert!(mapped(log, 0.5) > 0.5);
+        assert_close(mapped(log, 1.0), 1.0);
+    }
+
+    #[test]
+    fn no_value() {
+        // Fitting skips where the surface has no value
+        let map = HeightMap { range: InputRange::Fit, ..HeightMap::default() };
+        let surface = map.apply(Box::new(Ramp { gap: 0.5 }));
+        assert_close(surface.compute(0.75, 0.5), 0.5);
+        assert!(surface.compute(0.25, 0.5).is_nan());
+        assert_eq!(surface.compute_clamped(0.25, 0.5), 0.0);
+
+        // A surface with no values at all still gets a range
+        assert_eq!(fit(&Ramp { gap: 2.0 }), (0.0, 1.0));
+    }
+}
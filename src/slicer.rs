@@ -1,94 +1,915 @@
+use std::f64::consts::PI;
+
+use crate::contours::{self, ContourTracer};
+use crate::domain::Domain;
+use crate::families::{self, Family};
 use crate::heights::Height2D;
-use crate::geom::Vec2;
-use crate::polylines::{Polyline, Slice};
+use crate::intersections::{Crossing, Intersections};
+use crate::geom::{Vec2, Vec3};
+use crate::polylines::{Axis, Polyline, Slice, SliceId, Slit};
+use crate::positions::SlicePositions;
+use crate::solids::{Shape, Solid};
+
+// Solids are sampled on a grid over each slicing plane. Past this
+// resolution the grid gets too big to be worth it.
+const MAX_SOLID_RES: u32 = 9;
+// Bisection steps that put outline points on the surface of a solid
+const REFINE_STEPS: usize = 12;
+// Material thinner than this where two slices cross, in model units, can't
+// hold them together, so they don't get slits there
+const MIN_CROSSING_HEIGHT: f64 = 0.01;
+// The vertical axis of the model, seen from above
+const CENTER: Vec2 = Vec2(0.5, 0.5);
+
+/// Slices through the vertical axis at equal angles instead of a grid, with
+/// optional rings around the axis
+struct RadialPattern {
+    count: usize,
+    // Radii of the rings as fractions of the model's radius
+    rings: Vec<f64>,
+}
 
 pub struct Slicer {
-    slice_res: u32,
+    // Where the X slices cross the y-axis, and the Y slices the x-axis
+    x_positions: Vec<f64>,
+    y_positions: Vec<f64>,
     curve_res: u32,
-    surface: Box<dyn Height2D>
+    shape: Shape,
+    // Thickness of the material and the extra room left around it in
+    // notches, in model units. A thickness of 0 means single cut lines.
+    thickness: f64,
+    clearance: f64,
+    // How far up the material the slits of crossing slices meet
+    split_ratio: f64,
+    // Largest gap in model units between a curve and the outline that
+    // follows it, when curves are sampled adaptively
+    tolerance: Option<f64>,
+    // Height in model units of the strip added under every slice of a
+    // height field. 0 means no strip.
+    base: f64,
+    // Thickness of the base plate in model units, if there is one. It is
+    // also how far the tabs stick out under the slices.
+    plate_thickness: Option<f64>,
+    radial: Option<RadialPattern>,
+    // Families of slices at any angle, instead of X and Y slices
+    families: Vec<Family>,
+    // Where a height field stands, seen from above
+    domain: Domain,
+    // How far the model runs along y, and how tall it is at a height of 1,
+    // in units of its width along x
+    depth: f64,
+    height: f64,
 }
 
 impl Slicer {
-    pub fn new(
-            slice_res: u32, 
-            curve_res: u32, 
-            surface: 
-            Box<dyn Height2D>) 
-            -> Self {
+    pub fn new(slice_res: u32, curve_res: u32, shape: Shape) -> Self {
+        let positions = SlicePositions::from_resolution(slice_res).positions();
         Self {
-            slice_res,
+            x_positions: positions.clone(),
+            y_positions: positions,
             curve_res,
-            surface
+            shape,
+            thickness: 0.0,
+            clearance: 0.0,
+            split_ratio: 0.5,
+            tolerance: None,
+            base: 0.0,
+            plate_thickness: None,
+            radial: None,
+            families: Vec::new(),
+            domain: Domain::Square,
+            depth: 1.0,
+            height: 1.0,
+        }
+    }
+
+    /// Put the X slices at these positions along y instead of where the
+    /// slice resolution would
+    pub fn with_x_slices(mut self, positions: Vec<f64>) -> Self {
+        self.x_positions = positions;
+        self
+    }
+
+    /// Put the Y slices at these positions along x
+    pub fn with_y_slices(mut self, positions: Vec<f64>) -> Self {
+        self.y_positions = positions;
+        self
+    }
+
+    /// Make each slit a notch wide enough for material `thickness` thick
+    /// to slide through with `clearance` to spare, instead of a single cut.
+    /// Both are in model units, where the slices are 1 unit wide.
+    pub fn with_material(mut self, thickness: f64, clearance: f64) -> Self {
+        self.thickness = thickness;
+        self.clearance = clearance;
+        self
+    }
+
+    /// Where crossing slices meet, as a fraction of the height of the
+    /// material from the bottom. X slices are slit from the top down to
+    /// there and Y slices from the bottom up.
+    pub fn with_split_ratio(mut self, split_ratio: f64) -> Self {
+        self.split_ratio = split_ratio;
+        self
+    }
+
+    /// Sample height field curves adaptively rather than at `curve_res`
+    /// evenly spaced points. The outline stays within `tolerance` model
+    /// units of the curve.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = Some(tolerance);
+        self
+    }
+
+    /// Stand every slice of a height field on a strip this many model units
+    /// tall, so the finished model has a flat bottom to sit on
+    pub fn with_base(mut self, height: f64) -> Self {
+        self.base = height;
+        self
+    }
+
+    /// Add a base plate of this thickness in model units. Tabs under the
+    /// slices lock into slots in the plate, so it holds them upright.
+    pub fn with_base_plate(mut self, thickness: f64) -> Self {
+        self.plate_thickness = Some(thickness);
+        self
+    }
+
+    /// Cut `count` slices through the vertical axis at equal angles, plus a
+    /// ring at each of `rings` as a fraction of the way out from the axis,
+    /// instead of X and Y slices. The surface must be the same all the way
+    /// around, and every radial slice is cut to its profile along the
+    /// x-axis. The domain must be the square or a circle holding the rings.
+    pub fn with_radial(mut self, count: usize, rings: Vec<f64>) -> Self {
+        self.radial = Some(RadialPattern { count, rings });
+        self
+    }
+
+    /// Cut these families of slices at any angles instead of X and Y
+    /// slices. Where two families cross, the one given first is slit from
+    /// the top.
+    pub fn with_families(mut self, families: Vec<Family>) -> Self {
+        self.families = families;
+        self
+    }
+
+    /// Trim height field slices to this footprint. Slices that run off it
+    /// and back on come in more than one piece.
+    pub fn with_domain(mut self, domain: Domain) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Make the model `depth` along y and `height` tall, in units of its
+    /// width along x, instead of a cube. Lengths given to the slicer and
+    /// the slices it makes are in units of the width too.
+    pub fn with_size(mut self, depth: f64, height: f64) -> Self {
+        self.depth = depth;
+        self.height = height;
+        self
+    }
+
+    /// Width of a notch for a slice crossing at an angle with this sine
+    /// and cosine. Away from a right angle the other slice crosses on a
+    /// slant, and across this slice's own thickness it shifts along by
+    /// thickness * |cot|, so the notch needs to be wider than the material.
+    fn notch_width(&self, sine: f64, cosine: f64) -> f64 {
+        if self.thickness <= 0.0 {
+            return 0.0;
         }
-    } 
 
-    pub fn make_x_slices(&self) -> Vec<Slice> {
-        make_intervals(self.slice_res, false).into_iter().map(|y0| {
-            self.make_x_slice(y0) 
-        }).collect()
+        self.thickness * (1.0 + cosine.abs()) / sine.abs() + self.clearance
+    }
+
+    /// Width of a notch for a slice crossing at a right angle
+    fn slit_width(&self) -> f64 {
+        self.notch_width(1.0, 0.0)
     }
 
-    fn make_x_slice(&self, y0: f64) -> Slice {
-        let mut outline_vertices = vec![Vec2(0.0, 0.0), Vec2(1.0, 0.0)]; 
-        for x in make_intervals(self.curve_res, true).into_iter().rev() {
-            let height = clamp(self.surface.compute(x, y0));
-            outline_vertices.push(Vec2(x, height));
+    /// Where to sample a curve across the part of a slice from `start` to
+    /// `end`, in order. The slice is `length` long, which the tolerance
+    /// allows for.
+    fn curve_samples(
+            &self,
+            curve: &dyn Fn(f64) -> f64,
+            start: f64,
+            end: f64,
+            length: f64) -> Vec<f64> {
+        match self.tolerance {
+            Some(tolerance) => {
+                adaptive_samples(curve, start, end, length, tolerance)
+            },
+            None => make_intervals(self.curve_res, true)
+                .into_iter()
+                .map(|t| start + t * (end - start))
+                .collect(),
         }
-        let outline = Polyline::new(&outline_vertices, true);
+    }
 
-        let mut slits = Vec::new();
-        for x in make_intervals(self.slice_res, false).into_iter() {
-            let height = clamp(self.surface.compute(x, y0));
-            let slit = Polyline::new(&[
-                Vec2(x, height),
-                Vec2(x, height / 2.0)
-            ], false);
+    /// The stretches of the line from `start` to `end` over the domain, as
+    /// fractions of the way along it. Lines run right across the square,
+    /// so only other domains trim them.
+    fn stretches(&self, start: Vec2, end: Vec2) -> Vec<(f64, f64)> {
+        if self.domain.is_square() {
+            vec![(0.0, 1.0)]
+        } else {
+            self.domain.stretches(start, end)
+        }
+    }
+
+    /// The stretches of material on the vertical line through (x, y), from
+    /// the bottom up
+    fn spans(&self, x: f64, y: f64) -> Vec<(f64, f64)> {
+        match &self.shape {
+            Shape::HeightField(_) if !self.domain.contains(Vec2(x, y)) => {
+                Vec::new()
+            },
+            Shape::HeightField(surface) => {
+                // The base strip runs under the whole slice, so crossing
+                // slices always meet there even where the surface is at 0
+                let height = surface.compute_clamped(x, y) * self.height;
+                if height + self.base > 0.0 {
+                    vec![(-self.base, height)]
+                } else {
+                    Vec::new()
+                }
+            },
+            Shape::Solid(solid) => {
+                let field = |z: f64| solid.field(Vec3(x, y, z));
+                contours::find_spans(&field, self.solid_samples())
+                    .into_iter()
+                    .map(|(bottom, top)| {
+                        (bottom * self.height, top * self.height)
+                    })
+                    .collect()
+            },
+        }
+    }
+
+    /// Every X slice followed by every Y slice, then the base plate if
+    /// there is one
+    pub fn make_slices(&self) -> Vec<Slice> {
+        if let Some(pattern) = &self.radial {
+            return self.make_radial_slices(pattern);
+        }
+        if !self.families.is_empty() {
+            return self.make_family_slices(&self.families);
+        }
+
+        let intersections = Intersections::new(
+            &self.x_positions,
+            &self.y_positions,
+            |x, y| self.spans(x, y),
+            self.split_ratio,
+            MIN_CROSSING_HEIGHT);
 
-            slits.push(slit);
+        let mut slices = self.make_x_slices(&intersections);
+        slices.extend(self.make_y_slices(&intersections));
+        if self.plate_thickness.is_some() {
+            slices.push(self.make_base_plate());
         }
+        slices
+    }
+
+    fn make_x_slices(&self, intersections: &Intersections) -> Vec<Slice> {
+        self.x_positions
+            .iter()
+            .enumerate()
+            .map(|(i, &y0)| {
+                let outlines = match &self.shape {
+                    Shape::HeightField(surface) => {
+                        self.x_outlines(surface.as_ref(), y0)
+                    },
+                    Shape::Solid(solid) => {
+                        let point = |u: f64, v: f64| Vec3(u, y0, v);
+                        self.solid_outlines(solid.as_ref(), point, 1.0)
+                    },
+                };
+                let slits = intersections
+                    .along_x_slice(i)
+                    .map(|crossing| self.x_slit(crossing))
+                    .collect();
+                Slice::new(SliceId::new(Axis::X, i), outlines, slits)
+            })
+            // Planes that miss a solid or the domain leave nothing to cut
+            .filter(|slice| !slice.outlines().is_empty())
+            .collect()
+    }
+
+    /// Outlines of the x-slice at y = y0 under the curve of the surface,
+    /// one for each stretch over the domain
+    fn x_outlines(&self, surface: &dyn Height2D, y0: f64) -> Vec<Polyline> {
+        let tabs = tabs(&self.y_positions);
+        let curve = |x: f64| surface.compute_clamped(x, y0) * self.height;
+        self.stretches(Vec2(0.0, y0), Vec2(1.0, y0))
+            .into_iter()
+            .map(|(start, end)| {
+                let mut outline_vertices = self.bottom_edge(start, end, &tabs);
+                let samples = self.curve_samples(&curve, start, end, 1.0);
+                for x in samples.into_iter().rev() {
+                    outline_vertices.push(Vec2(x, curve(x)));
+                }
+
+                Polyline::new(&outline_vertices, true)
+            })
+            .collect()
+    }
 
+    /// The slit in an X slice at a crossing, from the top of the material
+    /// down to the split. It receives the Y slice from above.
+    fn x_slit(&self, crossing: &Crossing) -> Slit {
+        let spans_at = |offset: f64| self.spans(crossing.x + offset, crossing.y);
+        let top = notch_end(
+            spans_at, crossing.span(), crossing.split, true, self.slit_width());
+        let path = make_slit(crossing.x, top, crossing.split, self.slit_width());
 
-        Slice::new(outline, slits)
+        Slit::new(path, SliceId::new(Axis::Y, crossing.y_slice))
     }
 
-    pub fn make_y_slices(&self) -> Vec<Slice> {
-        make_intervals(self.slice_res, false).into_iter().map(|x0| {
-            self.make_y_slice(x0) 
-        }).collect()
+    fn make_y_slices(&self, intersections: &Intersections) -> Vec<Slice> {
+        self.y_positions
+            .iter()
+            .enumerate()
+            .map(|(j, &x0)| {
+                let outlines = match &self.shape {
+                    Shape::HeightField(surface) => {
+                        self.y_outlines(surface.as_ref(), x0)
+                    },
+                    Shape::Solid(solid) => {
+                        let point = |u: f64, v: f64| Vec3(x0, 1.0 - u, v);
+                        self.solid_outlines(solid.as_ref(), point, self.depth)
+                    },
+                };
+                let slits = intersections
+                    .along_y_slice(j)
+                    .map(|crossing| self.y_slit(crossing))
+                    .collect();
+                Slice::new(SliceId::new(Axis::Y, j), outlines, slits)
+            })
+            // Planes that miss a solid or the domain leave nothing to cut
+            .filter(|slice| !slice.outlines().is_empty())
+            .collect()
     }
 
-    fn make_y_slice(&self, x0: f64) -> Slice {
-        let mut outline_vertices = vec![Vec2(0.0, 0.0), Vec2(1.0, 0.0)]; 
-        for y in make_intervals(self.curve_res, true).into_iter() {
-            let height = clamp(self.surface.compute(x0, y));
-            outline_vertices.push(Vec2(1.0 - y, height));
+    /// Outlines of the y-slice at x = x0. Y slices are laid out with -y
+    /// across so their front faces -x once assembled, and are as long as
+    /// the model is deep.
+    fn y_outlines(&self, surface: &dyn Height2D, x0: f64) -> Vec<Polyline> {
+        let flipped: Vec<(f64, f64)> = tabs(&self.x_positions)
+            .into_iter()
+            .rev()
+            .map(|(start, end)| {
+                ((1.0 - end) * self.depth, (1.0 - start) * self.depth)
+            })
+            .collect();
+        let curve = |y: f64| surface.compute_clamped(x0, y) * self.height;
+        self.stretches(Vec2(x0, 0.0), Vec2(x0, 1.0))
+            .into_iter()
+            .map(|(start, end)| {
+                let (left, right) =
+                    ((1.0 - end) * self.depth, (1.0 - start) * self.depth);
+                let mut outline_vertices =
+                    self.bottom_edge(left, right, &flipped);
+                let samples = self.curve_samples(&curve, start, end, self.depth);
+                for y in samples.into_iter() {
+                    outline_vertices.push(Vec2((1.0 - y) * self.depth, curve(y)));
+                }
+
+                Polyline::new(&outline_vertices, true)
+            })
+            .collect()
+    }
+
+    /// The slit in a Y slice at a crossing, from the bottom of the material
+    /// up to the split. It slides down over the X slice.
+    fn y_slit(&self, crossing: &Crossing) -> Slit {
+        let spans_at = |offset: f64| {
+            self.spans(crossing.x, crossing.y + offset / self.depth)
+        };
+        let bottom = notch_end(
+            spans_at, crossing.span(), crossing.split, false, self.slit_width());
+        let u = (1.0 - crossing.y) * self.depth;
+        let path = make_slit(u, bottom, crossing.split, self.slit_width());
+
+        Slit::new(path, SliceId::new(Axis::X, crossing.x_slice))
+    }
+
+    /// The bottom of a height field slice from `left` to `right`. It is at
+    /// 0, or at the bottom of the base strip with a tab sticking down over
+    /// each of `tabs` between them if there is a base plate.
+    fn bottom_edge(&self, left: f64, right: f64, tabs: &[(f64, f64)])
+            -> Vec<Vec2> {
+        let bottom = -self.base;
+        let mut vertices = vec![Vec2(left, bottom)];
+        if let Some(depth) = self.plate_thickness {
+            let inside = tabs
+                .iter()
+                .filter(|&&(start, end)| left <= start && end <= right);
+            for &(start, end) in inside {
+                vertices.push(Vec2(start, bottom));
+                vertices.push(Vec2(start, bottom - depth));
+                vertices.push(Vec2(end, bottom - depth));
+                vertices.push(Vec2(end, bottom));
+            }
+        }
+        vertices.push(Vec2(right, bottom));
+
+        vertices
+    }
+
+    /// The base plate, seen from above, with a slot for every tab. X
+    /// slices run along x, so their slots do too, and Y slots run along y.
+    fn make_base_plate(&self) -> Slice {
+        let outline = Polyline::new(&[
+            Vec2(0.0, 0.0),
+            Vec2(1.0, 0.0),
+            Vec2(1.0, self.depth),
+            Vec2(0.0, self.depth),
+        ], true);
+
+        let mut slots = Vec::new();
+        for (i, &y) in self.x_positions.iter().enumerate() {
+            let y = y * self.depth;
+            for (start, end) in tabs(&self.y_positions) {
+                let (start, end) = (Vec2(start, y), Vec2(end, y));
+                let path = make_slot(start, end, self.slit_width());
+                slots.push(Slit::new(path, SliceId::new(Axis::X, i)));
+            }
+        }
+        for (j, &x) in self.y_positions.iter().enumerate() {
+            for (start, end) in tabs(&self.x_positions) {
+                let (start, end) =
+                    (Vec2(x, start * self.depth), Vec2(x, end * self.depth));
+                let path = make_slot(start, end, self.slit_width());
+                slots.push(Slit::new(path, SliceId::new(Axis::Y, j)));
+            }
         }
-        let outline = Polyline::new(&outline_vertices, true);
+
+        Slice::new(SliceId::new(Axis::Base, 0), vec![outline], slots)
+    }
+
+    /// Every radial slice followed by every ring
+    fn make_radial_slices(&self, pattern: &RadialPattern) -> Vec<Slice> {
+        let crossings = self.radial_crossings(pattern);
+        let mut slices: Vec<Slice> = (0..pattern.count)
+            .map(|k| self.radial_slice(k, pattern.count, &crossings))
+            .collect();
+        slices.extend(pattern.rings
+            .iter()
+            .enumerate()
+            .map(|(m, &fraction)| {
+                self.ring(m, fraction, pattern.count, &crossings)
+            }));
+
+        slices
+    }
+
+    /// Where each radial slice crosses each ring, on both sides of the
+    /// axis. The crossings use the radial slice as their X slice and the
+    /// ring as their Y slice.
+    fn radial_crossings(&self, pattern: &RadialPattern) -> Vec<Crossing> {
+        let mut crossings = Vec::new();
+        for k in 0..pattern.count {
+            for (m, &fraction) in pattern.rings.iter().enumerate() {
+                let radius = fraction / 2.0;
+                for &side in [-radius, radius].iter() {
+                    let Vec2(x, y) = CENTER
+                        .translate(spoke(k, pattern.count).scale(side));
+                    // Measured along the x-axis, like the radial slices
+                    for (bottom, top) in self.spans(0.5 + side, 0.5) {
+                        if top - bottom < MIN_CROSSING_HEIGHT {
+                            continue;
+                        }
+
+                        crossings.push(Crossing {
+                            x_slice: k,
+                            y_slice: m,
+                            x,
+                            y,
+                            bottom,
+                            top,
+                            split: bottom + self.split_ratio * (top - bottom),
+                        });
+                    }
+                }
+            }
+        }
+
+        crossings
+    }
+
+    /// Radial slice `k` of `count`, across the whole model through the
+    /// axis. It is laid out like an X slice when k is 0, and each one after
+    /// that is turned a bit further counterclockwise seen from above. The
+    /// rings slide down into slits from the top.
+    fn radial_slice(&self, k: usize, count: usize, crossings: &[Crossing])
+            -> Slice {
+        // Every slice has the profile along the x-axis, so they all come
+        // out the same
+        let point = |u: f64| CENTER.translate(Vec2(u - 0.5, 0.0));
+        let curve = |u: f64| self.height_at(point(u));
+        let outlines = self.stretches(point(0.0), point(1.0))
+            .into_iter()
+            .map(|(start, end)| self.curve_outline(&curve, start, end, 1.0))
+            .collect();
+
+        let mut slits = self.center_slits(k, count);
+        for crossing in crossings.iter().filter(|c| c.x_slice == k) {
+            let position = Vec2(crossing.x, crossing.y);
+            let u = 0.5 + position.sub(CENTER).dot(spoke(k, count));
+            let spans_at = |offset: f64| {
+                let Vec2(x, y) = point(u + offset);
+                self.spans(x, y)
+            };
+            let top = notch_end(
+                spans_at, crossing.span(), crossing.split, true, self.slit_width());
+            let path = make_slit(u, top, crossing.split, self.slit_width());
+            let ring = SliceId::new(Axis::Ring, crossing.y_slice);
+            slits.push(Slit::new(path, ring));
+        }
+
+        Slice::new(SliceId::new(Axis::Radial, k), outlines, slits)
+    }
+
+    /// Slits down the middle of radial slice `k` so all `count` of them
+    /// slide together on the axis
+    fn center_slits(&self, k: usize, count: usize) -> Vec<Slit> {
+        let span = match self.spans(0.5, 0.5).first() {
+            Some(&span) if count > 1 => span,
+            _ => return Vec::new(),
+        };
+        let slices: Vec<(SliceId, Vec2, f64)> = (0..count)
+            .map(|i| (SliceId::new(Axis::Radial, i), spoke(i, count), 0.5))
+            .collect();
+
+        self.stacked_slits(CENTER, span, &slices).swap_remove(k)
+    }
+
+    /// Slits for slices that all cross at `point`, seen from above, in a
+    /// stretch of material. Each slice is given with its direction and
+    /// where the point is across it, and gets its slits in the same order.
+    ///
+    /// Each slice keeps its own band of the material, in order from the
+    /// bottom up. It is slit from the top down to its band for the slice
+    /// after it, and from the bottom up to its band over the one before.
+    /// Two slices meet at the split ratio, more at even bands. Notches are
+    /// wide enough for the slice crossing at the narrowest angle to pass.
+    fn stacked_slits(
+            &self,
+            point: Vec2,
+            span: (f64, f64),
+            slices: &[(SliceId, Vec2, f64)]) -> Vec<Vec<Slit>> {
+        let count = slices.len();
+        let (bottom, top) = span;
+        let band = |i: usize| {
+            let ratio = if count == 2 {
+                self.split_ratio
+            } else {
+                i as f64 / count as f64
+            };
+            bottom + ratio * (top - bottom)
+        };
+
+        slices
+            .iter()
+            .enumerate()
+            .map(|(r, &(_, direction, u))| {
+                let width = slices
+                    .iter()
+                    .enumerate()
+                    .filter(|&(other, _)| other != r)
+                    .map(|(_, &(_, other, _))| {
+                        self.notch_width(
+                            direction.cross(other), direction.dot(other))
+                    })
+                    .fold(0.0, f64::max);
+                let spans_at = |offset: f64| {
+                    let Vec2(x, y) = point.translate(direction.scale(offset));
+                    self.spans(x, y)
+                };
+                let slit = |split: f64, at_top: bool, other: SliceId| {
+                    let end = notch_end(spans_at, span, split, at_top, width);
+                    Slit::new(make_slit(u, end, split, width), other)
+                };
+
+                let mut slits = Vec::new();
+                if r + 1 < count {
+                    slits.push(slit(band(r + 1), true, slices[r + 1].0));
+                }
+                if r > 0 {
+                    slits.push(slit(band(r), false, slices[r - 1].0));
+                }
+                slits
+            })
+            .collect()
+    }
+
+    /// Every slice of every family, family by family. Slices are laid out
+    /// running along their direction from where they enter the square.
+    fn make_family_slices(&self, families: &[Family]) -> Vec<Slice> {
+        let mut slits: Vec<Vec<Vec<Slit>>> = families
+            .iter()
+            .map(|family| (0..family.len()).map(|_| Vec::new()).collect())
+            .collect();
+
+        for junction in families::junctions(families) {
+            let slices: Vec<(SliceId, Vec2, f64)> = junction.slices
+                .iter()
+                .map(|&(f, i)| {
+                    let direction = families[f].direction();
+                    let (start, _) = families[f].line(i);
+                    let u = junction.point.sub(start).dot(direction);
+                    (SliceId::new(Axis::Family(f), i), direction, u)
+                })
+                .collect();
+
+            let Vec2(x, y) = junction.point;
+            for span in self.spans(x, y) {
+                let (bottom, top) = span;
+                if top - bottom < MIN_CROSSING_HEIGHT {
+                    continue;
+                }
+
+                let stacked = self.stacked_slits(junction.point, span, &slices);
+                for (&(f, i), slice_slits) in junction.slices.iter().zip(stacked) {
+                    slits[f][i].extend(slice_slits);
+                }
+            }
+        }
+
+        let mut slices = Vec::new();
+        for (f, (family, family_slits)) in families.iter().zip(slits).enumerate() {
+            for (i, slice_slits) in family_slits.into_iter().enumerate() {
+                let (start, length) = family.line(i);
+                let point = |t: f64| {
+                    start.translate(family.direction().scale(t * length))
+                };
+                let curve = |t: f64| self.height_at(point(t));
+                // Slices across the flat corners of a model have nothing
+                // to cut
+                let stretches = self.stretches(point(0.0), point(1.0));
+                let outlines: Vec<Polyline> = stretches
+                    .into_iter()
+                    .map(|(from, to)| {
+                        self.curve_outline(&curve, from, to, length)
+                    })
+                    .filter(|outline| {
+                        outline.vertices().iter().any(|&Vec2(_, v)| v > 0.0)
+                    })
+                    .collect();
+                if outlines.is_empty() {
+                    continue;
+                }
+
+                let id = SliceId::new(Axis::Family(f), i);
+                slices.push(Slice::new(id, outlines, slice_slits));
+            }
+        }
+
+        slices
+    }
+
+    /// Ring `m`, a strip that bends into a circle `fraction` of the way out
+    /// from the axis. Its ends meet halfway between two of the `count`
+    /// radial slices, and it runs counterclockwise seen from above with its
+    /// slits cut from the bottom up.
+    fn ring(&self, m: usize, fraction: f64, count: usize, crossings: &[Crossing])
+            -> Slice {
+        let radius = fraction / 2.0;
+        let length = 2.0 * PI * radius;
+        let start = PI / (2 * count) as f64;
+        let point = |angle: f64| {
+            CENTER.translate(Vec2(angle.cos(), angle.sin()).scale(radius))
+        };
+        let curve = |t: f64| self.height_at(point(start + 2.0 * PI * t));
+        let outline = self.curve_outline(&curve, 0.0, 1.0, length);
 
         let mut slits = Vec::new();
-        for y in make_intervals(self.slice_res, false).into_iter() {
-            let height = clamp(self.surface.compute(x0, y));
-            let slit = Polyline::new(&[
-                Vec2(1.0 - y, 0.0),
-                Vec2(1.0 - y, height / 2.0)
-            ], false);
+        for crossing in crossings.iter().filter(|c| c.y_slice == m) {
+            let Vec2(dx, dy) = Vec2(crossing.x, crossing.y).sub(CENTER);
+            let angle = dy.atan2(dx);
+            let spans_at = |offset: f64| {
+                let Vec2(x, y) = point(angle + offset / radius);
+                self.spans(x, y)
+            };
+            let bottom = notch_end(
+                spans_at, crossing.span(), crossing.split, false, self.slit_width());
+            let t = ((angle - start) / (2.0 * PI)).rem_euclid(1.0);
+            let path = make_slit(
+                t * length, bottom, crossing.split, self.slit_width());
+            let radial = SliceId::new(Axis::Radial, crossing.x_slice);
+            slits.push(Slit::new(path, radial));
+        }
 
-            slits.push(slit);
+        Slice::new(SliceId::new(Axis::Ring, m), vec![outline], slits)
+    }
+
+    /// Outline of a slice `length` long under `curve`, over the part of it
+    /// from `start` to `end` as fractions of the way along
+    fn curve_outline(
+            &self,
+            curve: &dyn Fn(f64) -> f64,
+            start: f64,
+            end: f64,
+            length: f64) -> Polyline {
+        let mut outline_vertices =
+            vec![Vec2(start * length, 0.0), Vec2(end * length, 0.0)];
+        let samples = self.curve_samples(curve, start, end, length);
+        for t in samples.into_iter().rev() {
+            outline_vertices.push(Vec2(t * length, curve(t)));
+        }
+
+        Polyline::new(&outline_vertices, true)
+    }
+
+    /// Height of the surface seen from above at `point`
+    fn height_at(&self, Vec2(x, y): Vec2) -> f64 {
+        match &self.shape {
+            Shape::HeightField(surface) => {
+                surface.compute_clamped(x, y) * self.height
+            },
+            Shape::Solid(_) => {
+                unreachable!("only height fields are sliced at angles")
+            },
         }
+    }
+
+    /// Samples per side of the grid used to trace solids
+    fn solid_samples(&self) -> usize {
+        1 << (self.curve_res.min(MAX_SOLID_RES) + 1)
+    }
+
+    /// Outlines of a cut through a solid, `length` long. `point` maps the
+    /// slice's own coordinates, across and up from 0 to 1, to the model.
+    fn solid_outlines(
+            &self,
+            solid: &dyn Solid,
+            point: impl Fn(f64, f64) -> Vec3,
+            length: f64) -> Vec<Polyline> {
+        ContourTracer::new(self.solid_samples())
+            .with_refinement(REFINE_STEPS)
+            .trace(&|u, v| solid.field(point(u, v)))
+            .into_iter()
+            .map(|outline| outline.stretch(length, self.height))
+            .collect()
+    }
+}
+
+/// Where the open end of a slit should be so a notch `width` wide clears
+/// the outline across its whole width. The slit runs from the top or bottom
+/// of `span` to `split`, and `spans_at` finds the stretches of material a
+/// given offset to the side of it.
+fn notch_end(
+        spans_at: impl Fn(f64) -> Vec<(f64, f64)>,
+        span: (f64, f64),
+        split: f64,
+        at_top: bool,
+        width: f64) -> f64 {
+    let (bottom, top) = span;
+    let mut end = if at_top { top } else { bottom };
 
-        Slice::new(outline, slits)
+    let half_width = width / 2.0;
+    if half_width <= 0.0 {
+        return end;
     }
+
+    for offset in [-half_width, half_width] {
+        for (b, t) in spans_at(offset) {
+            if b <= split && split <= t {
+                end = if at_top { end.max(t) } else { end.min(b) };
+            }
+        }
+    }
+
+    end
+}
+
+/// Which way radial slice `k` of `count` runs, seen from above
+fn spoke(k: usize, count: usize) -> Vec2 {
+    let angle = k as f64 * PI / count as f64;
+    Vec2(angle.cos(), angle.sin())
 }
 
+/// A vertical slit at x from height `from` to height `to`. With a width,
+/// this is a closed rectangular notch centered on x.
+fn make_slit(x: f64, from: f64, to: f64, width: f64) -> Polyline {
+    if width <= 0.0 {
+        return Polyline::new(&[Vec2(x, from), Vec2(x, to)], false);
+    }
+
+    let left = x - width / 2.0;
+    let right = x + width / 2.0;
+    Polyline::new(&[
+        Vec2(left, from),
+        Vec2(left, to),
+        Vec2(right, to),
+        Vec2(right, from),
+    ], true)
+}
 
-fn clamp(x: f64) -> f64 {
-    if x < 0.0 {
-        0.0
-    } else if x > 1.0 {
-        1.0
-    } else {
-        x
+/// A straight slot from `start` to `end`. With a width, this is a closed
+/// rectangle centered on the line between them.
+fn make_slot(start: Vec2, end: Vec2, width: f64) -> Polyline {
+    if width <= 0.0 {
+        return Polyline::new(&[start, end], false);
     }
+
+    let side = end.sub(start).normalize().perpendicular().scale(width / 2.0);
+    Polyline::new(&[
+        start.sub(side),
+        end.sub(side),
+        end.translate(side),
+        start.translate(side),
+    ], true)
+}
+
+/// Where the tabs go under a slice crossed by slices at `crossings`, as
+/// (start, end) pairs along it. Tabs keep clear of the crossings, where the
+/// slices' own tabs would collide, and sit in the middle half of the gaps
+/// at both ends and in the middle.
+fn tabs(crossings: &[f64]) -> Vec<(f64, f64)> {
+    let mut bounds = vec![0.0];
+    bounds.extend_from_slice(crossings);
+    bounds.push(1.0);
+
+    let gaps = crossings.len() + 1;
+    let mut chosen = vec![0, gaps / 2, gaps - 1];
+    chosen.dedup();
+
+    chosen
+        .into_iter()
+        .map(|gap| {
+            let (start, end) = (bounds[gap], bounds[gap + 1]);
+            let quarter = (end - start) / 4.0;
+            (start + quarter, end - quarter)
+        })
+        .collect()
+}
+
+/// Sample positions from `from` to `to` where straight lines between the
+/// samples stay within `tolerance` of the curve. Positions are stretched to
+/// `length` to measure how far the curve strays, as it is on the slice. Each
+/// piece of a coarse even grid is halved until the curve is close enough to
+/// the chord across it.
+fn adaptive_samples(
+        curve: &dyn Fn(f64) -> f64,
+        from: f64,
+        to: f64,
+        length: f64,
+        tolerance: f64) -> Vec<f64> {
+    const START_DEPTH: u32 = 4;
+    // Jumps in the curve never get close to a chord. This stops them
+    // from being split forever.
+    const MAX_DEPTH: u32 = 20;
+
+    let point = |x: f64| Vec2(x * length, curve(x));
+    let pieces = 1 << START_DEPTH;
+    let mut samples = vec![from];
+    for i in 0..pieces {
+        let start = from + (to - from) * i as f64 / pieces as f64;
+        let end = from + (to - from) * (i + 1) as f64 / pieces as f64;
+        subdivide(
+            &point,
+            (start, point(start)),
+            (end, point(end)),
+            tolerance,
+            MAX_DEPTH - START_DEPTH,
+            &mut samples);
+    }
+
+    samples
+}
+
+/// Add sample positions after `start` up to and including `end`, each given
+/// with its point on the slice. The curve is checked at the quarter points
+/// as well as the middle so bumps between samples aren't missed.
+fn subdivide(
+        point: &dyn Fn(f64) -> Vec2,
+        start: (f64, Vec2),
+        end: (f64, Vec2),
+        tolerance: f64,
+        depth: u32,
+        samples: &mut Vec<f64>) {
+    let (x0, p0) = start;
+    let (x1, p1) = end;
+    let chord = p1.sub(p0);
+    let distance = |t: f64| {
+        let x = x0 + t * (x1 - x0);
+        chord.cross(point(x).sub(p0)).abs() / chord.length()
+    };
+
+    let close_enough = [0.25, 0.5, 0.75]
+        .iter()
+        .all(|&t| distance(t) <= tolerance);
+    if depth == 0 || close_enough {
+        samples.push(x1);
+        return;
+    }
+
+    let xm = (x0 + x1) / 2.0;
+    let middle = (xm, point(xm));
+    subdivide(point, start, middle, tolerance, depth - 1, samples);
+    subdivide(point, middle, end, tolerance, depth - 1, samples);
 }
 
 fn make_intervals(max_depth: u32, include_endpoints: bool) -> Vec<f64> {
@@ -120,3 +941,293 @@ fn intervals(left: f64, right: f64, depth: u32, max_depth: u32) -> Vec<f64> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THICKNESS: f64 = 0.01;
+    const CLEARANCE: f64 = 0.001;
+
+    /// A surface at the same height everywhere
+    struct Flat(f64);
+
+    impl Height2D for Flat {
+        fn compute(&self, _x: f64, _y: f64) -> f64 {
+            self.0
+        }
+    }
+
+    fn flat() -> Slicer {
+        Slicer::new(1, 4, Shape::HeightField(Box::new(Flat(0.5))))
+            .with_material(THICKNESS, CLEARANCE)
+    }
+
+    /// Widths of the notches in every slice
+    fn notch_widths<'a>(slices: impl IntoIterator<Item = &'a Slice>)
+            -> Vec<f64> {
+        slices
+            .into_iter()
+            .flat_map(|slice| slice.slits())
+            .map(|slit| slit.path().get_bbox().width())
+            .collect()
+    }
+
+    fn assert_widths(widths: &[f64], expected: f64) {
+        assert!(!widths.is_empty());
+        for width in widths {
+            assert!((width - expected).abs() < 1e-9,
+                "{:?} != {}", widths, expected);
+        }
+    }
+
+    #[test]
+    fn notches_widen_at_an_angle() {
+        // Two slices crossing in the middle at each angle
+        for &degrees in [90.0, 60.0, 45.0_f64].iter() {
+            let angle = degrees.to_radians();
+            let slicer = flat().with_families(vec![
+                Family::new(0.0, vec![0.5]),
+                Family::new(angle, vec![0.5]),
+            ]);
+            let expected =
+                THICKNESS * (1.0 + angle.cos()) / angle.sin() + CLEARANCE;
+            assert_widths(&notch_widths(&slicer.make_slices()), expected);
+        }
+
+        // Against more than one slice, the notch fits the narrowest angle
+        let slicer = flat().with_families(vec![
+            Family::new(0.0, vec![0.5]),
+            Family::new(PI / 3.0, vec![0.5]),
+            Family::new(2.0 * PI / 3.0, vec![0.5]),
+        ]);
+        let expected = THICKNESS * 1.5 / (PI / 3.0).sin() + CLEARANCE;
+        assert_widths(&notch_widths(&slicer.make_slices()), expected);
+    }
+
+    #[test]
+    fn radial_notches_fit_the_angle_between_spokes() {
+        for &count in [2, 3, 4, 6].iter() {
+            let slicer = flat().with_radial(count, vec![0.5]);
+            let slices = slicer.make_slices();
+            let (radial, rings): (Vec<&Slice>, Vec<&Slice>) = slices
+                .iter()
+                .partition(|slice| slice.id().axis == Axis::Radial);
+            assert_eq!(radial.len(), count);
+
+            // Spokes slide together on the axis, where the nearest ones
+            // cross at pi / count, and the rings cross them square on
+            let angle = PI / count as f64;
+            let center: Vec<f64> = radial
+                .iter()
+                .flat_map(|slice| slice.slits())
+                .filter(|slit| slit.crossing().axis == Axis::Radial)
+                .map(|slit| slit.path().get_bbox().width())
+                .collect();
+            let expected =
+                THICKNESS * (1.0 + angle.cos().abs()) / angle.sin() + CLEARANCE;
+            assert_widths(&center, expected);
+            assert_widths(&notch_widths(rings), THICKNESS + CLEARANCE);
+        }
+    }
+
+    /// The furthest the curve strays from the lines between samples, with
+    /// positions stretched to `length`
+    fn largest_gap(curve: &dyn Fn(f64) -> f64, samples: &[f64], length: f64)
+            -> f64 {
+        let point = |x: f64| Vec2(x * length, curve(x));
+        let mut gap = 0.0_f64;
+        for pair in samples.windows(2) {
+            let (start, end) = (point(pair[0]), point(pair[1]));
+            let chord = end.sub(start);
+            for i in 1..100 {
+                let x = pair[0] + (pair[1] - pair[0]) * i as f64 / 100.0;
+                let offset = point(x).sub(start);
+                gap = gap.max(chord.cross(offset).abs() / chord.length());
+            }
+        }
+
+        gap
+    }
+
+    #[test]
+    fn adaptive_samples_stay_within_tolerance() {
+        let curve = |x: f64| 0.2 * (12.0 * x).sin() + 0.5 * x * x;
+        let tolerance = 1e-3;
+        for &length in [0.5, 1.0, 2.0_f64.sqrt(), 3.0, 2.0 * PI].iter() {
+            let samples = adaptive_samples(&curve, 0.1, 0.9, length, tolerance);
+            assert_eq!(samples.first(), Some(&0.1));
+            assert_eq!(samples.last(), Some(&0.9));
+            assert!(samples.windows(2).all(|pair| pair[0] < pair[1]));
+            let gap = largest_gap(&curve, &samples, length);
+            // Only the quarter points of each piece are checked, so the
+            // curve can stray a little further in between
+            assert!(gap <= 1.1 * tolerance, "{} off at length {}", gap, length);
+        }
+
+        // Slices that are longer need more samples to follow the same curve
+        let short = adaptive_samples(&curve, 0.0, 1.0, 1.0, tolerance);
+        let long = adaptive_samples(&curve, 0.0, 1.0, 3.0, tolerance);
+        assert!(short.len() < long.len());
+    }
+
+    #[test]
+    fn deep_y_slices_stay_within_tolerance() {
+        /// Waves running along y
+        struct Waves;
+
+        impl Height2D for Waves {
+            fn compute(&self, _x: f64, y: f64) -> f64 {
+                0.5 + 0.4 * (10.0 * y).sin()
+            }
+        }
+
+        let depth = 3.0;
+        let slicer = Slicer::new(0, 4, Shape::HeightField(Box::new(Waves)))
+            .with_x_slices(Vec::new())
+            .with_size(depth, 1.0)
+            .with_tolerance(1e-3);
+        let slices = slicer.make_slices();
+        assert_eq!(slices.len(), 1);
+        let outline = &slices[0].outlines()[0];
+        let samples: Vec<f64> = outline.vertices()[2..]
+            .iter()
+            .map(|&Vec2(u, _)| 1.0 - u / depth)
+            .collect();
+        let curve = |y: f64| Waves.compute(0.5, y);
+        assert!(largest_gap(&curve, &samples, depth) <= 1.1e-3);
+    }
+
+    /// Left, bottom, right and top of a path
+    fn extent(path: &Polyline) -> (f64, f64, f64, f64) {
+        let bbox = path.get_bbox();
+        let Vec2(left, bottom) = bbox.bottom_left();
+        (left, bottom, left + bbox.width(), bottom + bbox.height())
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    /// Where the tabs stick out under a slice standing `depth` below 0
+    fn tab_ranges(slice: &Slice, depth: f64) -> Vec<(f64, f64)> {
+        let corners: Vec<f64> = slice.outlines()[0]
+            .vertices()
+            .iter()
+            .filter(|&&Vec2(_, v)| close(v, -depth))
+            .map(|&Vec2(u, _)| u)
+            .collect();
+        corners.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    #[test]
+    fn tabs_line_up_with_the_slots() {
+        let depth = 1.5;
+        let slicer = flat().with_size(depth, 1.0).with_base_plate(THICKNESS);
+        let slices = slicer.make_slices();
+        let plate = slices.last().unwrap();
+        assert_eq!(plate.id().axis, Axis::Base);
+
+        for slice in slices.iter().filter(|s| s.id().axis != Axis::Base) {
+            let tabs = tab_ranges(slice, THICKNESS);
+            assert_eq!(tabs.len(), 3, "{}", slice.id());
+
+            // Slots for this slice, as ranges along it
+            let mut slots: Vec<(f64, f64)> = plate
+                .slits()
+                .iter()
+                .filter(|slot| slot.crossing() == slice.id())
+                .map(|slot| {
+                    let (left, bottom, right, top) = extent(slot.path());
+                    match slice.id().axis {
+                        Axis::X => {
+                            assert!(close(top - bottom, THICKNESS + CLEARANCE));
+                            (left, right)
+                        },
+                        // Y slices are laid out with -y across
+                        _ => {
+                            assert!(close(right - left, THICKNESS + CLEARANCE));
+                            (depth - top, depth - bottom)
+                        },
+                    }
+                })
+                .collect();
+            slots.sort_by(|a, b| a.0.total_cmp(&b.0));
+            assert_eq!(slots.len(), tabs.len());
+            for (&(a, b), &(c, d)) in tabs.iter().zip(slots.iter()) {
+                assert!(close(a, c) && close(b, d),
+                    "{}: tab {:?} in slot {:?}", slice.id(), (a, b), (c, d));
+            }
+
+            // Tabs keep clear of the notches where slices cross
+            for slit in slice.slits() {
+                let (left, _, right, _) = extent(slit.path());
+                for &(start, end) in tabs.iter() {
+                    assert!(end < left || right < start,
+                        "{}: tab {:?} under {}", slice.id(), (start, end),
+                        slit.crossing());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crossing_slits_meet_at_the_split() {
+        let slicer = flat().with_size(1.5, 1.0).with_split_ratio(0.3);
+        let slices = slicer.make_slices();
+        let slits_of = |axis: Axis| -> Vec<(SliceId, &Slit)> {
+            slices
+                .iter()
+                .filter(|slice| slice.id().axis == axis)
+                .flat_map(|slice| {
+                    slice.slits().iter().map(move |slit| (slice.id(), slit))
+                })
+                .collect()
+        };
+        let (x_slits, y_slits) = (slits_of(Axis::X), slits_of(Axis::Y));
+        assert_eq!(x_slits.len(), 9);
+        assert_eq!(y_slits.len(), 9);
+
+        for &(x_slice, x_slit) in x_slits.iter() {
+            let pair: Vec<&Slit> = y_slits
+                .iter()
+                .filter(|&&(y_slice, y_slit)| {
+                    y_slice == x_slit.crossing() && y_slit.crossing() == x_slice
+                })
+                .map(|&(_, slit)| slit)
+                .collect();
+            assert_eq!(pair.len(), 1, "{} and {}", x_slice, x_slit.crossing());
+
+            // X slices are slit from the top and Y slices from the bottom,
+            // and together they cut right through the material
+            let (_, x_bottom, _, x_top) = extent(x_slit.path());
+            let (_, y_bottom, _, y_top) = extent(pair[0].path());
+            assert!(close(x_top, 0.5) && close(y_bottom, 0.0));
+            assert!(close(x_bottom, 0.15) && close(y_top, 0.15));
+        }
+    }
+
+    #[test]
+    fn y_slices_are_as_long_as_the_model_is_deep() {
+        let slices = flat().with_size(1.5, 0.8).make_slices();
+        for slice in slices.iter() {
+            let bbox = slice.get_bbox();
+            let length = match slice.id().axis {
+                Axis::X => 1.0,
+                _ => 1.5,
+            };
+            assert!(close(bbox.width(), length), "{}", slice.id());
+            assert!(close(bbox.height(), 0.4), "{}", slice.id());
+        }
+    }
+
+    #[test]
+    fn right_angles_fit_the_material() {
+        let widths = notch_widths(&flat().make_slices());
+        assert_widths(&widths, THICKNESS + CLEARANCE);
+
+        // Without a thickness, slits are single cut lines
+        let cut_lines = flat().with_material(0.0, 0.0);
+        assert_widths(&notch_widths(&cut_lines.make_slices()), 0.0);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::geom::Vec3;
+use crate::heights::Height2D;
+
+/// A closed 3D shape inside the unit cube, described by a field that is
+/// negative inside the solid and positive outside. The field doesn't have
+/// to be a true distance, but it should change smoothly so the slicer can
+/// find the boundary between samples.
+pub trait Solid {
+    fn field(&self, p: Vec3) -> f64;
+}
+
+/// What the slicer can cut: a height field z = f(x, y) over the unit square,
+/// or a solid
+pub enum Shape {
+    HeightField(Box<dyn Height2D>),
+    Solid(Box<dyn Solid>),
+}
+
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f64,
+}
+
+impl Solid for Sphere {
+    fn field(&self, p: Vec3) -> f64 {
+        let Vec3(x, y, z) = p;
+        let Vec3(cx, cy, cz) = self.center;
+
+        Vec3(x - cx, y - cy, z - cz).length() - self.radius
+    }
+}
+
+/// A donut lying flat, with its hole along the z-axis
+pub struct Torus {
+    pub center: Vec3,
+    /// Distance from the center to the middle of the tube
+    pub major_radius: f64,
+    /// Radius of the tube
+    pub minor_radius: f64,
+}
+
+impl Solid for Torus {
+    fn field(&self, p: Vec3) -> f64 {
+        let Vec3(x, y, z) = p;
+        let Vec3(cx, cy, cz) = self.center;
+
+        let from_axis = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+        let from_tube = from_axis - self.major_radius;
+        (from_tube * from_tube + (z - cz).powi(2)).sqrt() - self.minor_radius
+    }
+}
+
+pub enum Operation {
+    Union,
+    Intersection,
+    /// The first solid minus all the others
+    Difference,
+}
+
+/// Combines solids. The result is exact for inside/outside but only
+/// approximates a distance, which is all the slicer needs.
+pub struct Combination {
+    pub operation: Operation,
+    pub solids: Vec<Box<dyn Solid>>,
+}
+
+impl Solid for Combination {
+    fn field(&self, p: Vec3) -> f64 {
+        let mut fields = self.solids.iter().map(|solid| solid.field(p));
+        match self.operation {
+            Operation::Union => fields.fold(f64::INFINITY, f64::min),
+            Operation::Intersection => fields.fold(f64::NEG_INFINITY, f64::max),
+            Operation::Difference => {
+                let first = fields.next().unwrap_or(f64::INFINITY);
+                fields.fold(first, |result, field| result.max(-field))
+            },
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use std::f64::consts::PI;
-
 use crate::heights::{Height1D, Height2D};
 
 pub enum DistanceMetric {
@@ -44,66 +42,26 @@ fn to_polar(x: f64, y: f64, metric: &DistanceMetric) -> (f64, f64) {
     (r, theta)
 }
 
-pub struct SineHill {}
-
-impl SineHill {
-    pub fn new() -> Self {
-        Self {}
-    }
-}
-
-impl Height2D for SineHill {
-    fn compute(&self, x: f64, y: f64) -> f64 {
-        const FREQ: f64 = 1.58;
-        const PHASE: f64 = 2.0;
-        let sine = 0.5 + 0.25 * (2.0 * PI * FREQ * x - PHASE).sin();
-
-        const SHARPNESS:f64 = 6.0;
-        let shifted = y - 0.5;
-        let hill = (-SHARPNESS * shifted * shifted).exp();
-
-        sine * hill
-    }
-}
-
-type HeightFn = fn(f64) -> f64;
-
-pub struct HeightFunction {
-    height: HeightFn
-}
-
-impl HeightFunction {
-    pub fn new(height: HeightFn) -> Self {
-        Self {
-            height
-        }
-    }
-}
-
-impl Height1D for HeightFunction {
-    fn compute(&self, x: f64) -> f64 {
-        (self.height)(x)
-    }
-}
-
+/// Multiplies a profile along x with a profile along y
 pub struct ProductSurface {
-    x_func: HeightFn,
-    y_func: HeightFn,
+    x_profile: Box<dyn Height1D>,
+    y_profile: Box<dyn Height1D>,
 }
 
 impl ProductSurface {
-    pub fn new(x_func: HeightFn, y_func: HeightFn) -> Self {
+    pub fn new(x_profile: Box<dyn Height1D>, y_profile: Box<dyn Height1D>)
+            -> Self {
         Self {
-            x_func,
-            y_func,
+            x_profile,
+            y_profile,
         }
     }
 }
 
 impl Height2D for ProductSurface {
     fn compute(&self, x: f64, y:f64) -> f64 {
-        let x = (self.x_func)(x);
-        let y = (self.y_func)(y);
+        let x = self.x_profile.compute(x);
+        let y = self.y_profile.compute(y);
 
         x * y
     }
@@ -0,0 +1,233 @@
+use std::io::{self, Write};
+
+use crate::backend::{Backend, OpenFile, Style};
+use crate::geom::Vec2;
+use crate::polylines::Polyline;
+use crate::units::Unit;
+
+/// Writes one SVG file per page. The viewBox is in real-world units so
+/// cutting software imports the pieces at the right size.
+pub struct SvgBackend<W: Write> {
+    open_file: OpenFile<W>,
+    unit: Unit,
+    page_number: usize,
+    page_size: (f64, f64),
+    // Polylines are collected into one group per style
+    bboxes: Vec<u8>,
+    outlines: Vec<u8>,
+    slits: Vec<u8>,
+    labels: Vec<u8>,
+}
+
+impl<W: Write> SvgBackend<W> {
+    // Stroke width in points
+    const STROKE_WIDTH: f64 = 0.5;
+
+    pub fn new(open_file: OpenFile<W>, unit: Unit) -> Self {
+        Self {
+            open_file,
+            unit,
+            page_number: 0,
+            page_size: (0.0, 0.0),
+            bboxes: Vec::new(),
+            outlines: Vec::new(),
+            slits: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    fn write_group(file: &mut W, id: &str, color: &str, body: &[u8])
+            -> io::Result<()> {
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(
+            file,
+            "<g id=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\">",
+            id,
+            color,
+            Self::STROKE_WIDTH)?;
+        file.write_all(body)?;
+        writeln!(file, "</g>")
+    }
+}
+
+impl<W: Write> Backend for SvgBackend<W> {
+    fn begin_page(&mut self, width: f64, height: f64) -> io::Result<()> {
+        self.page_number += 1;
+        self.page_size = (width, height);
+
+        Ok(())
+    }
+
+    fn draw_polyline(&mut self, polyline: &Polyline, style: Style)
+            -> io::Result<()> {
+        let vertices = polyline.vertices();
+
+        // Don't write individual points.
+        if vertices.len() < 2 {
+            return Ok(());
+        }
+
+        let points: Vec<String> = vertices
+            .iter()
+            .map(|Vec2(x, y)| format!("{},{}", x, y))
+            .collect();
+
+        let group = match style {
+            Style::Outline => &mut self.outlines,
+            Style::Slit => &mut self.slits,
+            Style::BoundingBox => &mut self.bboxes,
+            Style::Label => &mut self.labels,
+        };
+        writeln!(group, "<polyline points=\"{}\"/>", points.join(" "))
+    }
+
+    fn draw_text(&mut self, text: &str, position: Vec2, size: f64, angle: f64)
+            -> io::Result<()> {
+        // Undo the flip of the enclosing group so the text is upright. The
+        // flip also reverses the direction of rotation.
+        let Vec2(x, y) = position;
+        writeln!(
+            self.labels,
+            "<text transform=\"translate({} {}) scale(1 -1) rotate({})\" \
+            font-family=\"Helvetica, sans-serif\" font-size=\"{}\" \
+            fill=\"blue\" stroke=\"none\">{}</text>",
+            x,
+            y,
+            -angle,
+            size,
+            escape_xml(text))
+    }
+
+    fn end_page(&mut self) -> io::Result<()> {
+        let mut file = (self.open_file)(self.page_number)?;
+
+        let points = self.unit.points();
+        let (width, height) = self.page_size;
+        let width = round(width / points);
+        let height = round(height / points);
+        let suffix = self.unit.suffix();
+
+        writeln!(file, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            file,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+            width=\"{0}{2}\" height=\"{1}{2}\" viewBox=\"0 0 {0} {1}\">",
+            width,
+            height,
+            suffix)?;
+
+        // The layout is in points with y pointing up, so flip it and
+        // convert to the units of the viewBox.
+        writeln!(
+            file,
+            "<g transform=\"translate(0 {}) scale({} {})\">",
+            height,
+            1.0 / points,
+            -1.0 / points)?;
+
+        Self::write_group(&mut file, "bboxes", "gray", &self.bboxes)?;
+        Self::write_group(&mut file, "outlines", "black", &self.outlines)?;
+        Self::write_group(&mut file, "slits", "red", &self.slits)?;
+        Self::write_group(&mut file, "labels", "blue", &self.labels)?;
+
+        writeln!(file, "</g>")?;
+        writeln!(file, "</svg>")?;
+        file.flush()?;
+
+        self.bboxes.clear();
+        self.outlines.clear();
+        self.slits.clear();
+        self.labels.clear();
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Trim floating point noise like 215.89999999999998 from the page size
+fn round(x: f64) -> f64 {
+    const PRECISION: f64 = 1e6;
+    (x * PRECISION).round() / PRECISION
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::{draw_piece, MemoryFiles};
+
+    fn render(unit: Unit) -> String {
+        let files = MemoryFiles::default();
+        draw_piece(&mut SvgBackend::new(files.opener(), unit)).unwrap();
+        let mut contents = files.contents();
+        assert_eq!(contents.len(), 1);
+        contents.remove(0)
+    }
+
+    #[test]
+    fn view_box_in_inches() {
+        let svg = render(Unit::Inches);
+        assert!(svg.starts_with("<?xml version=\"1.0\""));
+        assert!(svg.contains(
+            "width=\"8.5in\" height=\"11in\" viewBox=\"0 0 8.5 11\">"));
+        assert!(svg.contains(&format!(
+            "<g transform=\"translate(0 11) scale({0} -{0})\">", 1.0 / 72.0)));
+        assert!(svg.ends_with("</g>\n</svg>\n"));
+    }
+
+    #[test]
+    fn view_box_in_millimeters() {
+        let svg = render(Unit::Millimeters);
+        assert!(svg.contains("width=\"215.9mm\" height=\"279.4mm\""));
+        assert!(svg.contains("viewBox=\"0 0 215.9 279.4\">"));
+        assert!(svg.contains("translate(0 279.4)"));
+    }
+
+    #[test]
+    fn groups_by_style() {
+        let svg = render(Unit::Inches);
+        // Nothing was drawn as a bounding box, so that group is left out
+        assert!(!svg.contains("id=\"bboxes\""));
+        let group = |id: &str, color: &str| {
+            svg.find(&format!(
+                "<g id=\"{}\" fill=\"none\" stroke=\"{}\"", id, color))
+        };
+        let outlines = group("outlines", "black");
+        let slits = group("slits", "red");
+        let labels = group("labels", "blue");
+        assert!(outlines.is_some() && outlines < slits && slits < labels);
+        assert!(svg.contains(
+            "<polyline points=\"72,72 144,72 144,144 72,144 72,72\"/>"));
+        assert!(svg.contains("<polyline points=\"108,144 108,108\"/>"));
+        assert!(svg.contains(">X1</text>"));
+    }
+
+    #[test]
+    fn one_file_per_page() {
+        let files = MemoryFiles::default();
+        let mut backend = SvgBackend::new(files.opener(), Unit::Inches);
+        for &(width, height) in [(612.0, 792.0), (792.0, 612.0)].iter() {
+            backend.begin_page(width, height).unwrap();
+            backend.draw_text("<&>", Vec2(0.0, 0.0), 6.0, 90.0).unwrap();
+            backend.end_page().unwrap();
+        }
+        backend.finish().unwrap();
+
+        let contents = files.contents();
+        assert_eq!(contents.len(), 2);
+        assert!(contents[1].contains("viewBox=\"0 0 11 8.5\""));
+        assert!(contents[1].contains("rotate(-90)"));
+        assert!(contents[1].contains(">&lt;&amp;&gt;</text>"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+
+// 72 points per inch
+pub const INCH: f64 = 72.0;
+
+/// Real-world length units for output files.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Unit {
+    Inches,
+    Millimeters,
+}
+
+impl Unit {
+    pub fn points(&self) -> f64 {
+        match self {
+            Unit::Inches => INCH,
+            Unit::Millimeters => INCH / 25.4,
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Unit::Inches => "in",
+            Unit::Millimeters => "mm",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.suffix())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in" | "inch" | "inches" => Ok(Unit::Inches),
+            "mm" | "millimeters" => Ok(Unit::Millimeters),
+            _ => Err(format!("unknown unit '{}', expected in or mm", s)),
+        }
+    }
+}
+
+/// Parse a length like "0.5", "0.5in" or "12mm" into points. Plain numbers
+/// are inches.
+pub fn parse_length(s: &str) -> Result<f64, String> {
+    let number_end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(number_end);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a length like 0.5in or 12mm", s))?;
+    let unit = if unit.is_empty() {
+        Unit::Inches
+    } else {
+        unit.parse()?
+    };
+
+    Ok(value * unit.points())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths() {
+        assert_eq!(parse_length("0.5"), Ok(36.0));
+        assert_eq!(parse_length("2in"), Ok(144.0));
+        assert_eq!(parse_length("25.4mm"), Ok(72.0));
+        assert_eq!(parse_length("-1inch"), Ok(-72.0));
+    }
+
+    #[test]
+    fn errors() {
+        for s in ["", "mm", "1.2.3in", "3cm", "in3", "1 in"].iter() {
+            assert!(parse_length(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn unit_names() {
+        assert_eq!("millimeters".parse(), Ok(Unit::Millimeters));
+        assert_eq!(Unit::Inches.to_string(), "in");
+        assert!("cm".parse::<Unit>().is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use crate::geom::Vec2;
+use crate::polylines::{BBox, Polyline, Slice, SliceId};
+use crate::units::Unit;
+
+// Scan lines per direction are capped so huge pieces don't take forever
+const MAX_SCAN_LINES: usize = 2000;
+
+/// The smallest sizes a piece can have and still hold together, in points.
+/// A limit of 0 turns its check off.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Limits {
+    /// Narrowest stretch of material
+    pub min_width: f64,
+    /// Least material between two slits side by side
+    pub min_slit_gap: f64,
+    /// Least material left past the end of a slit
+    pub min_joint: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    /// The piece will be fragile
+    Warning,
+    /// The piece falls apart
+    Error,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Problem {
+    /// Material narrower than the minimum width, like a spike or a thin
+    /// strip along the bottom
+    Thin { width: f64 },
+    /// Two slits with too little material between them
+    SlitsClose { gap: f64 },
+    /// Too little material past the closed end of the slit for a crossing
+    /// slice
+    WeakJoint { remaining: f64, crossing: SliceId },
+}
+
+/// The worst spot on a slice with one kind of problem. Positions are from
+/// the bottom left corner of the slice, as it is drawn.
+pub struct Issue {
+    pub slice: SliceId,
+    pub problem: Problem,
+    pub position: Vec2,
+    /// How many spots on the slice have this kind of problem
+    pub count: usize,
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self.problem {
+            Problem::SlitsClose { gap } if gap <= 0.0 => Severity::Error,
+            Problem::WeakJoint { remaining, .. } if remaining <= 0.0 => {
+                Severity::Error
+            },
+            _ => Severity::Warning,
+        }
+    }
+
+    /// Say what is wrong, with lengths in `unit`
+    pub fn describe(&self, unit: Unit, limits: &Limits) -> String {
+        let length = |points: f64| format!("{:.3}{}", points / unit.points(), unit);
+        let Vec2(x, y) = self.position;
+        let at = format!("at ({}, {})", length(x), length(y));
+
+        let message = match self.problem {
+            Problem::Thin { width } => format!(
+                "material only {} wide {} (minimum {})",
+                length(width), at, length(limits.min_width)),
+            Problem::SlitsClose { gap } if gap <= 0.0 => {
+                format!("slits overlap {}", at)
+            },
+            Problem::SlitsClose { gap } => format!(
+                "only {} between slits {} (minimum {})",
+                length(gap), at, length(limits.min_slit_gap)),
+            Problem::WeakJoint { remaining, crossing } if remaining <= 0.0 => {
+                format!("the slit for {} cuts right through {}", crossing, at)
+            },
+            Problem::WeakJoint { remaining, crossing } => format!(
+                "only {} of material past the slit for {} {} (minimum {})",
+                length(remaining), crossing, at, length(limits.min_joint)),
+        };
+
+        if self.count > 1 {
+            format!("slice {}: {}, {} spots like this",
+                self.slice, message, self.count)
+        } else {
+            format!("slice {}: {}", self.slice, message)
+        }
+    }
+}
+
+/// Look for fragile spots on a slice measured in points. Each kind of
+/// problem is reported once per slice, at its worst spot.
+pub fn check_slice(slice: &Slice, limits: &Limits) -> Vec<Issue> {
+    let origin = slice.get_bbox().bottom_left();
+    let spots = vec![
+        thin_spots(slice, limits.min_width),
+        close_slits(slice, limits.min_slit_gap),
+        weak_joints(slice, limits.min_joint),
+    ];
+
+    spots
+        .into_iter()
+        .filter_map(|spots| {
+            let count = spots.len();
+            spots
+                .into_iter()
+                .min_by(|(a, _), (b, _)| size(a).total_cmp(&size(b)))
+                .map(|(problem, position)| Issue {
+                    slice: slice.id(),
+                    problem,
+                    position: position.sub(origin),
+                    count,
+                })
+        })
+        .collect()
+}
+
+fn size(problem: &Problem) -> f64 {
+    match *problem {
+        Problem::Thin { width } => width,
+        Problem::SlitsClose { gap } => gap,
+        Problem::WeakJoint { remaining, .. } => remaining,
+    }
+}
+
+/// Stretches of material narrower than `min_width`, found with scan lines
+/// across and up the slice. The tip of a point is always narrow, so a spot
+/// only counts if there's material `min_width` further along on both
+/// sides: then it is a thin neck, strip or spike rather than a tip.
+fn thin_spots(slice: &Slice, min_width: f64) -> Vec<(Problem, Vec2)> {
+    if min_width <= 0.0 {
+        return Vec::new();
+    }
+
+    let bbox = slice.get_bbox();
+    let Vec2(left, bottom) = bbox.bottom_left();
+    let mut spots = Vec::new();
+    for &transpose in [false, true].iter() {
+        // Scan lines run across when not transposed, and up when they are
+        let (start, extent) = if transpose {
+            (left, bbox.width())
+        } else {
+            (bottom, bbox.height())
+        };
+        let step = (min_width / 2.0).max(extent / MAX_SCAN_LINES as f64);
+        let flip = |Vec2(a, b): Vec2| {
+            if transpose { Vec2(b, a) } else { Vec2(a, b) }
+        };
+
+        let mut line = start + step / 2.0;
+        while line < start + extent {
+            for (from, to) in scan(slice.outlines(), line, transpose) {
+                let width = to - from;
+                let middle = (from + to) / 2.0;
+                let before = flip(Vec2(middle, line - min_width));
+                let after = flip(Vec2(middle, line + min_width));
+                if width < min_width && slice.contains(before) &&
+                        slice.contains(after) {
+                    spots.push((Problem::Thin { width }, flip(Vec2(middle, line))));
+                }
+            }
+            line += step;
+        }
+    }
+
+    spots
+}
+
+/// Pairs of slits side by side with less than `min_gap` between them
+fn close_slits(slice: &Slice, min_gap: f64) -> Vec<(Problem, Vec2)> {
+    if min_gap <= 0.0 {
+        return Vec::new();
+    }
+
+    let boxes: Vec<(f64, f64, f64, f64)> = slice.slits()
+        .iter()
+        .map(|slit| edges(&slit.path().get_bbox()))
+        .collect();
+
+    let mut spots = Vec::new();
+    for (i, &(left_a, right_a, bottom_a, top_a)) in boxes.iter().enumerate() {
+        for &(left_b, right_b, bottom_b, top_b) in boxes[i + 1..].iter() {
+            let bottom = bottom_a.max(bottom_b);
+            let top = top_a.min(top_b);
+            if bottom >= top {
+                continue;
+            }
+
+            let gap = (left_b - right_a).max(left_a - right_b);
+            if gap < min_gap {
+                let x = (right_a.min(right_b) + left_a.max(left_b)) / 2.0;
+                let y = (bottom + top) / 2.0;
+                spots.push((Problem::SlitsClose { gap }, Vec2(x, y)));
+            }
+        }
+    }
+
+    spots
+}
+
+/// Slits with less than `min_joint` of material past their closed end.
+/// Slits are cut in from an edge, so the closed end is the one with more
+/// material past it. If neither has any, the slit cuts right through.
+fn weak_joints(slice: &Slice, min_joint: f64) -> Vec<(Problem, Vec2)> {
+    if min_joint <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut spots = Vec::new();
+    for slit in slice.slits().iter() {
+        let (left, right, bottom, top) = edges(&slit.path().get_bbox());
+        let x = (left + right) / 2.0;
+        let runs = scan(slice.outlines(), x, true);
+
+        let below = runs
+            .iter()
+            .find(|(from, to)| *from < bottom && bottom <= *to)
+            .map_or(0.0, |(from, _)| bottom - from);
+        let above = runs
+            .iter()
+            .find(|(from, to)| *from <= top && top < *to)
+            .map_or(0.0, |(_, to)| to - top);
+
+        let (remaining, end) = if below >= above {
+            (below, bottom)
+        } else {
+            (above, top)
+        };
+        if remaining < min_joint {
+            let problem = Problem::WeakJoint {
+                remaining,
+                crossing: slit.crossing(),
+            };
+            spots.push((problem, Vec2(x, end)));
+        }
+    }
+
+    spots
+}
+
+/// Where a horizontal line at height `line` is inside the outlines, as
+/// (from, to) pairs along it. Transposed, the line is vertical at x = `line`
+/// and the pairs are heights.
+fn scan(outlines: &[Polyline], line: f64, transpose: bool) -> Vec<(f64, f64)> {
+    let mut crossings = Vec::new();
+    for outline in outlines.iter() {
+        for pair in outline.vertices().windows(2) {
+            let (Vec2(x1, y1), Vec2(x2, y2)) = if transpose {
+                let (Vec2(y1, x1), Vec2(y2, x2)) = (pair[0], pair[1]);
+                (Vec2(x1, y1), Vec2(x2, y2))
+            } else {
+                (pair[0], pair[1])
+            };
+
+            if (y1 > line) != (y2 > line) {
+                crossings.push(x1 + (line - y1) / (y2 - y1) * (x2 - x1));
+            }
+        }
+    }
+
+    crossings.sort_by(f64::total_cmp);
+    crossings
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Left, right, bottom and top of a bounding box
+fn edges(bbox: &BBox) -> (f64, f64, f64, f64) {
+    let Vec2(left, bottom) = bbox.bottom_left();
+    (left, left + bbox.width(), bottom, bottom + bbox.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polylines::{Axis, Slit};
+
+    /// A straight slit from one end to the other
+    type Line = ((f64, f64), (f64, f64));
+
+    const LIMITS: Limits = Limits {
+        min_width: 5.0,
+        min_slit_gap: 5.0,
+        min_joint: 5.0,
+    };
+
+    fn slice(outline: &[(f64, f64)], slits: &[Line]) -> Slice {
+        let points: Vec<Vec2> = outline.iter().map(|&(x, y)| Vec2(x, y)).collect();
+        let slits = slits
+            .iter()
+            .map(|&((x1, y1), (x2, y2))| {
+                let path = Polyline::new(&[Vec2(x1, y1), Vec2(x2, y2)], false);
+                Slit::new(path, SliceId::new(Axis::Y, 0))
+            })
+            .collect();
+        Slice::new(
+            SliceId::new(Axis::X, 0),
+            vec![Polyline::new(&points, true)],
+            slits)
+    }
+
+    fn rectangle(slits: &[Line]) -> Slice {
+        slice(&[(0.0, 0.0), (100.0, 0.0), (100.0, 50.0), (0.0, 50.0)], slits)
+    }
+
+    fn problems(slice: &Slice, limits: &Limits) -> Vec<(Problem, Severity)> {
+        check_slice(slice, limits)
+            .iter()
+            .map(|issue| (issue.problem, issue.severity()))
+            .collect()
+    }
+
+    #[test]
+    fn sturdy_piece() {
+        let piece = rectangle(&[((50.0, 50.0), (50.0, 25.0))]);
+        assert!(check_slice(&piece, &LIMITS).is_empty());
+    }
+
+    #[test]
+    fn thin_neck() {
+        // Two blocks joined by a bar 2 points tall
+        let dumbbell = slice(&[
+            (0.0, 0.0), (50.0, 0.0), (50.0, 24.0), (80.0, 24.0), (80.0, 0.0),
+            (130.0, 0.0), (130.0, 50.0), (80.0, 50.0), (80.0, 26.0),
+            (50.0, 26.0), (50.0, 50.0), (0.0, 50.0),
+        ], &[]);
+        let issues = check_slice(&dumbbell, &LIMITS);
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].problem, Problem::Thin { width }
+            if (width - 2.0).abs() < 1e-9));
+        assert_eq!(issues[0].severity(), Severity::Warning);
+        let Vec2(x, y) = issues[0].position;
+        assert!((50.0..=80.0).contains(&x) && (y - 25.0).abs() < 1e-9);
+
+        let off = Limits { min_width: 0.0, ..LIMITS };
+        assert!(check_slice(&dumbbell, &off).is_empty());
+    }
+
+    #[test]
+    fn close_and_overlapping_slits() {
+        let close = rectangle(&[
+            ((10.0, 50.0), (10.0, 30.0)),
+            ((12.0, 50.0), (12.0, 30.0)),
+        ]);
+        assert_eq!(problems(&close, &LIMITS),
+            vec![(Problem::SlitsClose { gap: 2.0 }, Severity::Warning)]);
+
+        let overlapping = rectangle(&[
+            ((10.0, 50.0), (10.0, 30.0)),
+            ((10.0, 40.0), (10.0, 20.0)),
+        ]);
+        assert_eq!(problems(&overlapping, &LIMITS)[0].1, Severity::Error);
+    }
+
+    #[test]
+    fn weak_joints() {
+        let crossing = SliceId::new(Axis::Y, 0);
+        let deep = rectangle(&[((50.0, 50.0), (50.0, 2.0))]);
+        assert_eq!(problems(&deep, &LIMITS), vec![(
+            Problem::WeakJoint { remaining: 2.0, crossing },
+            Severity::Warning)]);
+
+        let through = rectangle(&[((50.0, 50.0), (50.0, 0.0))]);
+        let issues = check_slice(&through, &LIMITS);
+        assert_eq!(issues[0].severity(), Severity::Error);
+        assert_eq!(
+            issues[0].describe(Unit::Inches, &LIMITS),
+            "slice X1: the slit for Y1 cuts right through at (0.694in, 0.000in)");
+    }
+}